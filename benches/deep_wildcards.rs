@@ -0,0 +1,80 @@
+//! Times wildcard searches over a synthetic file of deeply nested modules.
+//!
+//! Run with `cargo bench --bench deep_wildcards`. Results are borrowed so the times measure
+//! the search rather than cloning the results. Each path is searched both anchored and
+//! unanchored, and the ratio between them shows the cost of looking for the start of a path
+//! inside every item. The paths ending in `zzz` match nothing, so they measure that cost alone.
+//! The paths with a segment between two `**` reach each item through several ancestors, so
+//! they measure how much of that overlap the search avoids walking again.
+//!
+//! Times vary between machines, so compare runs on the same machine before and after a
+//! change to measure its effect.
+
+use std::time::{Duration, Instant};
+
+use syn_select::Selector;
+
+/// Build a module tree `depth` levels deep, with `width` modules in each module, plus a few
+/// items of other kinds at every level.
+fn nested_mods(depth: usize, width: usize) -> String {
+    let mut src = String::from(
+        "pub struct S { a: u8, b: u16 }
+        impl S { fn new() -> Self { S { a: 0, b: 0 } } fn get(&self) -> u8 { self.a } }
+        pub fn f() { struct Local; }
+        ",
+    );
+
+    if depth > 0 {
+        let inner = nested_mods(depth - 1, width);
+        for index in 0..width {
+            src.push_str(&format!("pub mod m{} {{ {} }}\n", index, inner));
+        }
+    }
+
+    src
+}
+
+fn time(file: &syn::File, path: &str, anchored: bool) -> (usize, Duration) {
+    let selector = Selector::try_from(path).unwrap().anchored(anchored);
+    let iterations = 10;
+    let start = Instant::now();
+    let mut count = 0;
+    for _ in 0..iterations {
+        count = selector.apply_to_borrowed(file).len();
+    }
+
+    (count, start.elapsed() / iterations)
+}
+
+fn main() {
+    let file: syn::File = syn::parse_str(&nested_mods(5, 4)).unwrap();
+    println!(
+        "{:<28} {:>19} {:>19} {:>6}",
+        "path", "anchored", "unanchored", "ratio"
+    );
+    for path in &[
+        "_::_::_::_",
+        "_::_::_::_::_::_",
+        "m0::_::S::get",
+        "_::_::_::_::_::_::Local",
+        "zzz",
+        "_::zzz",
+        "_::_::zzz",
+        "**::S",
+        "**::_::**::S",
+        "**::_::_::**::get",
+        "_::**::Local",
+    ] {
+        let (anchored_count, anchored) = time(&file, path, true);
+        let (unanchored_count, unanchored) = time(&file, path, false);
+        println!(
+            "{:<28} {:>8} {:>10.2?} {:>8} {:>10.2?} {:>6.1}",
+            path,
+            anchored_count,
+            anchored,
+            unanchored_count,
+            unanchored,
+            unanchored.as_secs_f64() / anchored.as_secs_f64().max(f64::EPSILON)
+        );
+    }
+}
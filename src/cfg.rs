@@ -0,0 +1,156 @@
+//! The `cfg` predicates guarding search results.
+
+use std::fmt;
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Item, Lit, Meta, Token};
+
+use crate::search::Attrs;
+
+/// A condition from a `#[cfg(...)]` attribute, such as `feature = "h"` or `not(test)`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CfgPredicate {
+    /// A bare option, such as `test` or `unix`.
+    Option(String),
+    /// A key and value, such as `feature = "h"`.
+    KeyValue { key: String, value: String },
+    /// `all(...)`, which holds when every predicate holds.
+    All(Vec<CfgPredicate>),
+    /// `any(...)`, which holds when at least one predicate holds.
+    Any(Vec<CfgPredicate>),
+    /// `not(...)`, which holds when its predicate doesn't.
+    Not(Box<CfgPredicate>),
+    /// Tokens that aren't a valid predicate, kept as written.
+    Other(String),
+}
+
+impl CfgPredicate {
+    /// Get the predicate of a `#[cfg(...)]` attribute, or `None` for other attributes.
+    pub(crate) fn from_attr(attr: &Attribute) -> Option<Self> {
+        if !attr.path().is_ident("cfg") {
+            return None;
+        }
+
+        Some(match attr.parse_args::<Meta>() {
+            Ok(meta) => CfgPredicate::from_meta(&meta),
+            Err(_) => CfgPredicate::Other(attr.meta.to_token_stream().to_string()),
+        })
+    }
+
+    fn from_meta(meta: &Meta) -> Self {
+        let other = || CfgPredicate::Other(meta.to_token_stream().to_string());
+        match meta {
+            Meta::Path(path) => match path.get_ident() {
+                Some(ident) => CfgPredicate::Option(ident.to_string()),
+                None => other(),
+            },
+            Meta::NameValue(meta) => match (meta.path.get_ident(), &meta.value) {
+                (
+                    Some(key),
+                    Expr::Lit(ExprLit {
+                        lit: Lit::Str(value),
+                        ..
+                    }),
+                ) => CfgPredicate::KeyValue {
+                    key: key.to_string(),
+                    value: value.value(),
+                },
+                _ => other(),
+            },
+            Meta::List(list) => {
+                let args =
+                    match list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated) {
+                        Ok(args) => args.iter().map(CfgPredicate::from_meta).collect::<Vec<_>>(),
+                        Err(_) => return other(),
+                    };
+
+                if list.path.is_ident("all") {
+                    CfgPredicate::All(args)
+                } else if list.path.is_ident("any") {
+                    CfgPredicate::Any(args)
+                } else if list.path.is_ident("not") && args.len() == 1 {
+                    CfgPredicate::Not(Box::new(args.into_iter().next().unwrap()))
+                } else {
+                    other()
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CfgPredicate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let list = |f: &mut fmt::Formatter, name: &str, predicates: &[CfgPredicate]| {
+            write!(f, "{}(", name)?;
+            for (index, predicate) in predicates.iter().enumerate() {
+                if index > 0 {
+                    f.write_str(", ")?;
+                }
+                write!(f, "{}", predicate)?;
+            }
+            f.write_str(")")
+        };
+
+        match self {
+            CfgPredicate::Option(name) => f.write_str(name),
+            CfgPredicate::KeyValue { key, value } => write!(f, "{} = {:?}", key, value),
+            CfgPredicate::All(predicates) => list(f, "all", predicates),
+            CfgPredicate::Any(predicates) => list(f, "any", predicates),
+            CfgPredicate::Not(predicate) => write!(f, "not({})", predicate),
+            CfgPredicate::Other(tokens) => f.write_str(tokens),
+        }
+    }
+}
+
+/// A search result with the `cfg` predicates that must all hold for it to be compiled.
+#[derive(Clone)]
+pub struct Match {
+    /// The predicates from the result's own `cfg` attributes and those of the items it was
+    /// found in, outermost first.
+    ///
+    /// For results merged by [`crate::Selector::merge_cfg_variants`], these are only the
+    /// predicates shared by every variant.
+    pub cfgs: Vec<CfgPredicate>,
+    /// The matched item. For merged results, this is the first variant's item.
+    pub item: Item,
+    /// The results at the same path under different `cfg` predicates, in document order, when
+    /// the selector merges them. This is empty for results that weren't merged.
+    pub variants: Vec<Match>,
+}
+
+impl Match {
+    /// Merge results that share a path into one, keeping them as its variants.
+    pub(crate) fn merge(mut variants: Vec<Match>) -> Self {
+        if variants.len() == 1 {
+            return variants.remove(0);
+        }
+
+        let first = &variants[0];
+        Match {
+            cfgs: first
+                .cfgs
+                .iter()
+                .filter(|cfg| variants.iter().all(|variant| variant.cfgs.contains(cfg)))
+                .cloned()
+                .collect(),
+            item: first.item.clone(),
+            variants,
+        }
+    }
+}
+
+impl From<Item> for Match {
+    fn from(item: Item) -> Self {
+        Match {
+            cfgs: item
+                .attrs()
+                .unwrap_or_default()
+                .iter()
+                .filter_map(CfgPredicate::from_attr)
+                .collect(),
+            item,
+            variants: Vec::new(),
+        }
+    }
+}
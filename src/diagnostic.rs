@@ -0,0 +1,51 @@
+//! Records of the decisions a search makes, for explaining unexpected results.
+
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// A decision made during a search, such as matching an item or finding nothing inside it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// The position in the selector of the segment being sought, counting from zero.
+    pub depth: usize,
+    /// The names of the items the search had descended through when it made the decision.
+    pub path: Vec<String>,
+    /// A description of the decision.
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "(root) depth {}: {}", self.depth, self.message)
+        } else {
+            write!(
+                f,
+                "{} depth {}: {}",
+                self.path.join("::"),
+                self.depth,
+                self.message
+            )
+        }
+    }
+}
+
+/// The diagnostics collected by a search and the nested searches it starts.
+#[derive(Clone, Default)]
+pub(crate) struct Diagnostics(Arc<Mutex<Vec<Diagnostic>>>);
+
+impl Diagnostics {
+    pub(crate) fn push(&self, diagnostic: Diagnostic) {
+        if let Ok(mut diagnostics) = self.0.lock() {
+            diagnostics.push(diagnostic);
+        }
+    }
+
+    /// Take the diagnostics collected so far.
+    pub(crate) fn take(&self) -> Vec<Diagnostic> {
+        self.0
+            .lock()
+            .map(|mut diagnostics| std::mem::take(&mut *diagnostics))
+            .unwrap_or_default()
+    }
+}
@@ -1,14 +1,29 @@
 use std::fmt;
 
+use crate::ItemKind;
+
 /// An error encountered while parsing or executing a selector.
 #[derive(Debug)]
 pub struct Error {
     kind: ErrorKind,
+    detail: Option<String>,
 }
 
 impl Error {
     fn new(kind: ErrorKind) -> Self {
-        Error { kind }
+        Error { kind, detail: None }
+    }
+
+    /// Get the kind of error that occurred, so callers can handle each kind differently.
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    /// Get the message of the underlying error, if there was one. For an
+    /// [`ErrorKind::InvalidSegment`], this is `syn`'s explanation of why the segment isn't an
+    /// identifier, such as `expected identifier, found keyword `fn``.
+    pub fn detail(&self) -> Option<&str> {
+        self.detail.as_deref()
     }
 
     /// Create an error indicating the caller provided an empty path to search.
@@ -16,10 +31,41 @@ impl Error {
         Error::new(ErrorKind::EmptyPath)
     }
 
+    /// Create an error indicating the path has nothing between two separators, or before or
+    /// after one.
+    pub(crate) fn empty_segment(position: usize) -> Self {
+        Error::new(ErrorKind::EmptySegment(position))
+    }
+
     /// Create an error indicating the caller provided a non-empty string that
-    /// couldn't be parsed to a searchable path.
-    pub(crate) fn invalid_segment(segment: String) -> Self {
-        Error::new(ErrorKind::InvalidSegment(segment))
+    /// couldn't be parsed to a searchable path, with the error `syn` gave when parsing it
+    /// as an identifier if there was one.
+    pub(crate) fn invalid_segment(segment: String, source: Option<syn::Error>) -> Self {
+        Error {
+            kind: ErrorKind::InvalidSegment(segment),
+            detail: source.map(|source| source.to_string()),
+        }
+    }
+
+    /// Create an error indicating no item matched the segment at `position`, and that no
+    /// later segments were matched because of it.
+    pub(crate) fn not_found(position: usize, segment: String, is_leaf: bool) -> Self {
+        if is_leaf {
+            Error::new(ErrorKind::LeafNotFound { position, segment })
+        } else {
+            Error::new(ErrorKind::ContainerNotFound { position, segment })
+        }
+    }
+
+    /// Create an error indicating a search found results, but none of the expected kind.
+    pub(crate) fn kind_mismatch(expected: ItemKind, found: ItemKind) -> Self {
+        Error::new(ErrorKind::KindMismatch { expected, found })
+    }
+
+    /// Create an error indicating the caller provided source text that couldn't
+    /// be parsed as a Rust file.
+    pub(crate) fn parse(error: syn::Error) -> Self {
+        Error::new(ErrorKind::Parse(error))
     }
 }
 
@@ -27,22 +73,88 @@ impl std::error::Error for Error {}
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_summary(f)?;
+        match &self.detail {
+            Some(detail) => write!(f, ": {}", detail),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Error {
+    fn fmt_summary(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match &self.kind {
             ErrorKind::EmptyPath => f.write_str("Empty path"),
+            ErrorKind::EmptySegment(position) => write!(
+                f,
+                "Empty path segment at position {}: separators must be exactly `::` with an identifier on each side",
+                position
+            ),
+            ErrorKind::InvalidSegment(segment) if segment == "**" => f.write_str(
+                "Invalid path segment: `**` must be followed by the segment to find at any depth",
+            ),
+            ErrorKind::InvalidSegment(segment) if segment.contains(':') => write!(
+                f,
+                "Invalid path segment: `{}` contains a stray `:`; segments are separated by `::`",
+                segment
+            ),
+            ErrorKind::InvalidSegment(segment)
+                if segment.bytes().all(|byte| byte.is_ascii_digit()) =>
+            {
+                write!(
+                    f,
+                    "Invalid path segment: `{}` is not a field index; indices are written without leading zeros",
+                    segment
+                )
+            }
             ErrorKind::InvalidSegment(segment) => write!(
                 f,
                 "Invalid path segment: `{}` is not an identifier",
                 segment
             ),
+            ErrorKind::Parse(error) => write!(f, "Unable to parse source: {}", error),
+            ErrorKind::ContainerNotFound { position, segment } => write!(
+                f,
+                "No item matched `{}` at position {} of the path, so nothing inside it could match",
+                segment, position
+            ),
+            ErrorKind::LeafNotFound { position, segment } => write!(
+                f,
+                "No item matched `{}` at the end of the path (position {})",
+                segment, position
+            ),
+            ErrorKind::KindMismatch { expected, found } => write!(
+                f,
+                "Expected a result of kind `{}`, but found `{}`",
+                expected, found
+            ),
         }
     }
 }
 
+/// The different kinds of [`Error`].
+///
+/// More kinds may be added in the future, so matches against this type need a wildcard arm.
 #[derive(Debug)]
-enum ErrorKind {
+#[non_exhaustive]
+pub enum ErrorKind {
     /// The selector parser was passed an empty string.
     EmptyPath,
+    /// The selector parser was passed a path with an empty segment, such as `a::::b`, `::a`
+    /// or `a::`. This holds the position of the empty segment, counting from zero.
+    EmptySegment(usize),
     /// The selector parser was passed a non-empty string that had
     /// an invalid part after being split by the path separator.
     InvalidSegment(String),
+    /// The source text to search could not be parsed.
+    Parse(syn::Error),
+    /// A segment before the end of the path matched nothing, so the search couldn't look
+    /// inside it for the rest of the path. `position` counts segments from zero.
+    ContainerNotFound { position: usize, segment: String },
+    /// Every segment up to the last one matched, but nothing matched the last segment.
+    /// `position` counts segments from zero.
+    LeafNotFound { position: usize, segment: String },
+    /// The path matched, but none of the results had the expected kind. `found` is the kind
+    /// of the first result.
+    KindMismatch { expected: ItemKind, found: ItemKind },
 }
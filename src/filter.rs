@@ -0,0 +1,106 @@
+//! Conditions on the attributes of the items a search matches.
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Lit, Meta, Path, Token};
+
+/// A condition a matched item's attributes must satisfy for it to be a result.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum Filter {
+    /// An attribute argument with the given path and value. The path `serde::rename` and
+    /// value `x` match `#[serde(rename = "x")]`, and the path `must_use` and value `reason`
+    /// match `#[must_use = "reason"]`.
+    AttrArg { path: Vec<String>, value: String },
+    /// A trait in a `#[derive(...)]` list, compared by the last segment of its path.
+    Derives(String),
+    /// The presence, or with `false` the absence, of `#[automatically_derived]`.
+    AutomaticallyDerived(bool),
+}
+
+impl Filter {
+    /// Check if the attributes of an item satisfy this filter.
+    pub(crate) fn matches(&self, attrs: &[Attribute]) -> bool {
+        match self {
+            Filter::AttrArg { path, value } => attrs.iter().any(|attr| has_arg(attr, path, value)),
+            Filter::Derives(name) => attrs.iter().any(|attr| derives(attr, name)),
+            Filter::AutomaticallyDerived(generated) => {
+                attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("automatically_derived"))
+                    == *generated
+            }
+        }
+    }
+}
+
+/// Check if `attr` is `#[path = value]`, or `#[attr(key = value)]` where `path` is the
+/// attribute's path followed by `key`.
+fn has_arg(attr: &Attribute, path: &[String], value: &str) -> bool {
+    match &attr.meta {
+        Meta::NameValue(meta) => path_is(&meta.path, path) && value_is(&meta.value, value),
+        Meta::List(list) => {
+            let (key, attr_path) = match path.split_last() {
+                Some(split) => split,
+                None => return false,
+            };
+
+            if !path_is(&list.path, attr_path) {
+                return false;
+            }
+
+            // Arguments that aren't a list of metas, such as `#[cfg_attr(...)]` contents
+            // with unusual syntax, can't contain a match.
+            list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .map(|args| {
+                    args.iter().any(|arg| match arg {
+                        Meta::NameValue(arg) => {
+                            arg.path.is_ident(key) && value_is(&arg.value, value)
+                        }
+                        _ => false,
+                    })
+                })
+                .unwrap_or(false)
+        }
+        Meta::Path(_) => false,
+    }
+}
+
+/// Check if `attr` is a `#[derive(...)]` list that includes a trait named `name`, such as
+/// `Serialize` or `serde::Serialize`.
+fn derives(attr: &Attribute, name: &str) -> bool {
+    if !attr.path().is_ident("derive") {
+        return false;
+    }
+
+    attr.parse_args_with(Punctuated::<Path, Token![,]>::parse_terminated)
+        .map(|paths| {
+            paths
+                .iter()
+                .any(|path| path.segments.last().is_some_and(|seg| seg.ident == name))
+        })
+        .unwrap_or(false)
+}
+
+/// Check if a syntax path has exactly the given segments.
+fn path_is(path: &Path, segments: &[String]) -> bool {
+    path.segments.len() == segments.len()
+        && path
+            .segments
+            .iter()
+            .zip(segments)
+            .all(|(seg, own)| seg.ident == own)
+}
+
+/// Check if an argument value equals `value`. String literals are compared by their contents,
+/// and other expressions by their tokens, ignoring whitespace.
+fn value_is(expr: &Expr, value: &str) -> bool {
+    if let Expr::Lit(ExprLit {
+        lit: Lit::Str(lit), ..
+    }) = expr
+    {
+        return lit.value() == value;
+    }
+
+    let condense = |s: &str| s.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+    condense(&expr.to_token_stream().to_string()) == condense(value)
+}
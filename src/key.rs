@@ -0,0 +1,52 @@
+//! Structural equality for search results, so they can be deduplicated in sets and maps.
+
+use std::hash::{Hash, Hasher};
+
+use quote::ToTokens;
+use syn::Item;
+
+/// An item compared and hashed by its tokens, as [`crate::same_item`] compares items.
+///
+/// This lets results from several searches be combined in a `HashSet` without keeping
+/// duplicates.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use std::collections::HashSet;
+/// use syn_select::ItemKey;
+///
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+/// let mut results = HashSet::new();
+/// for path in &["a::b", "_::_fn", "a::_"] {
+///     results.extend(syn_select::select(path, &file).unwrap().into_iter().map(ItemKey));
+/// }
+/// assert_eq!(results.len(), 1);
+/// ```
+#[derive(Clone)]
+pub struct ItemKey(pub Item);
+
+impl ItemKey {
+    fn tokens(&self) -> String {
+        self.0.to_token_stream().to_string()
+    }
+}
+
+impl PartialEq for ItemKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.tokens() == other.tokens()
+    }
+}
+
+impl Eq for ItemKey {}
+
+impl Hash for ItemKey {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.tokens().hash(state);
+    }
+}
+
+impl From<Item> for ItemKey {
+    fn from(item: Item) -> Self {
+        ItemKey(item)
+    }
+}
@@ -0,0 +1,135 @@
+//! The kinds of items a search can return.
+
+use std::fmt;
+
+use syn::Item;
+
+/// The kind of an item, such as a function or a struct.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum ItemKind {
+    Const,
+    Enum,
+    ExternCrate,
+    Fn,
+    ForeignMod,
+    Impl,
+    Macro,
+    Mod,
+    Static,
+    Struct,
+    Trait,
+    TraitAlias,
+    Type,
+    Union,
+    Use,
+    /// Tokens `syn` could not parse into a more specific kind of item.
+    Verbatim,
+}
+
+impl ItemKind {
+    /// Get the kind of an item.
+    pub fn of(item: &Item) -> Self {
+        match item {
+            Item::Const(_) => ItemKind::Const,
+            Item::Enum(_) => ItemKind::Enum,
+            Item::ExternCrate(_) => ItemKind::ExternCrate,
+            Item::Fn(_) => ItemKind::Fn,
+            Item::ForeignMod(_) => ItemKind::ForeignMod,
+            Item::Impl(_) => ItemKind::Impl,
+            Item::Macro(_) => ItemKind::Macro,
+            Item::Mod(_) => ItemKind::Mod,
+            Item::Static(_) => ItemKind::Static,
+            Item::Struct(_) => ItemKind::Struct,
+            Item::Trait(_) => ItemKind::Trait,
+            Item::TraitAlias(_) => ItemKind::TraitAlias,
+            Item::Type(_) => ItemKind::Type,
+            Item::Union(_) => ItemKind::Union,
+            Item::Use(_) => ItemKind::Use,
+            Item::Verbatim(_) => ItemKind::Verbatim,
+            _ => ItemKind::Verbatim,
+        }
+    }
+
+    /// Get the kind with the given name, as it appears in selectors like `_fn`.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        ALL.iter().copied().find(|kind| kind.name() == name)
+    }
+
+    /// Get the rank of this kind when results are grouped by kind, as documentation
+    /// tools list them: imports and modules, then types and traits, then functions and
+    /// values, then impls.
+    pub(crate) fn group_rank(self) -> usize {
+        GROUP_ORDER
+            .iter()
+            .position(|kind| *kind == self)
+            .unwrap_or(GROUP_ORDER.len())
+    }
+
+    /// Get the name of the kind, as it appears in selectors like `_fn`.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ItemKind::Const => "const",
+            ItemKind::Enum => "enum",
+            ItemKind::ExternCrate => "extern_crate",
+            ItemKind::Fn => "fn",
+            ItemKind::ForeignMod => "foreign_mod",
+            ItemKind::Impl => "impl",
+            ItemKind::Macro => "macro",
+            ItemKind::Mod => "mod",
+            ItemKind::Static => "static",
+            ItemKind::Struct => "struct",
+            ItemKind::Trait => "trait",
+            ItemKind::TraitAlias => "trait_alias",
+            ItemKind::Type => "type",
+            ItemKind::Union => "union",
+            ItemKind::Use => "use",
+            ItemKind::Verbatim => "verbatim",
+        }
+    }
+}
+
+const ALL: [ItemKind; 16] = [
+    ItemKind::Const,
+    ItemKind::Enum,
+    ItemKind::ExternCrate,
+    ItemKind::Fn,
+    ItemKind::ForeignMod,
+    ItemKind::Impl,
+    ItemKind::Macro,
+    ItemKind::Mod,
+    ItemKind::Static,
+    ItemKind::Struct,
+    ItemKind::Trait,
+    ItemKind::TraitAlias,
+    ItemKind::Type,
+    ItemKind::Union,
+    ItemKind::Use,
+    ItemKind::Verbatim,
+];
+
+/// The order of the kinds when results are grouped by kind.
+const GROUP_ORDER: [ItemKind; 16] = [
+    ItemKind::ExternCrate,
+    ItemKind::Use,
+    ItemKind::Mod,
+    ItemKind::Macro,
+    ItemKind::Struct,
+    ItemKind::Enum,
+    ItemKind::Union,
+    ItemKind::Type,
+    ItemKind::Trait,
+    ItemKind::TraitAlias,
+    ItemKind::Fn,
+    ItemKind::Const,
+    ItemKind::Static,
+    ItemKind::Impl,
+    ItemKind::ForeignMod,
+    ItemKind::Verbatim,
+];
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -16,14 +16,43 @@
 //! assert_eq!(results.len(), 1);
 //! ```
 
-use syn::Item;
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::ops::Range;
 
+use proc_macro2::Span;
+use quote::ToTokens;
+use syn::{
+    parse_quote, Fields, Generics, Ident, ImplItem, ImplItemConst, ImplItemFn, ImplItemType, Item,
+    ItemStruct, Signature, Stmt, TraitItem, Type, Visibility,
+};
+
+mod cfg;
+mod diagnostic;
 mod error;
+mod filter;
+mod key;
+mod kind;
+mod offsets;
+mod outcome;
+mod outline;
+mod remove;
+mod render;
 mod search;
 mod selector;
+mod tree;
 
-pub use self::error::Error;
+pub use self::cfg::{CfgPredicate, Match};
+pub use self::diagnostic::Diagnostic;
+pub use self::error::{Error, ErrorKind};
+pub use self::key::ItemKey;
+pub use self::kind::ItemKind;
+pub use self::outcome::SelectOutcome;
+pub use self::outline::{outline, OutlineNode};
+pub use self::render::render;
+pub use self::search::Search;
 pub use self::selector::Selector;
+pub use self::tree::MatchNode;
 
 /// Parse a path, then search a file for all results that exactly match the specified
 /// path.
@@ -37,11 +66,782 @@ pub fn select(path: &str, file: &syn::File) -> Result<Vec<Item>, Error> {
     Ok(Selector::try_from(path)?.apply_to(file))
 }
 
+/// Parse a path, then search a file for all results that exactly match it, like [`select`],
+/// but say whether anything was found rather than returning an empty list.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use syn_select::SelectOutcome;
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+/// match syn_select::select_outcome("a::c", &file).unwrap() {
+///     SelectOutcome::NotFound => {}
+///     SelectOutcome::Found(_) => panic!("`a::c` doesn't exist"),
+/// }
+/// ```
+pub fn select_outcome(path: &str, file: &syn::File) -> Result<SelectOutcome, Error> {
+    Ok(select(path, file)?.into())
+}
+
+/// Parse a path, then search a sequence of items for all results that exactly match it, as
+/// if the items were the top level of a file.
+///
+/// This is useful in procedural macros, where the input is a token stream of items rather
+/// than a whole source file.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let tokens = quote::quote! {
+///     struct Config;
+///     impl Config {
+///         fn load() {}
+///     }
+/// };
+/// let items = syn::parse2::<syn::File>(tokens).unwrap().items;
+/// let results = syn_select::select_from_items("Config::load", &items).unwrap();
+/// assert_eq!(results.len(), 1);
+/// ```
+pub fn select_from_items(path: &str, items: &[Item]) -> Result<Vec<Item>, Error> {
+    Ok(Selector::try_from(path)?.apply_to_items(items))
+}
+
+/// Parse a path, then search several files, such as the files of a crate, for all results
+/// that exactly match it. Each file is searched as if it were the only one, and the results
+/// are returned in the order of the files they came from.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let lib: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+/// let other: syn::File = syn::parse_str("mod a { fn b() {} fn c() {} }").unwrap();
+/// let results = syn_select::select_many("a::b", &[lib, other]).unwrap();
+/// assert_eq!(results.len(), 2);
+/// ```
+pub fn select_many<'f>(
+    path: &str,
+    files: impl IntoIterator<Item = &'f syn::File>,
+) -> Result<Vec<Item>, Error> {
+    let selector = Selector::try_from(path)?;
+    Ok(files
+        .into_iter()
+        .flat_map(|file| selector.apply_to(file))
+        .collect())
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified
+/// path, borrowing the results that appear verbatim in the file instead of cloning them.
+///
+/// Results that the search had to change, such as a trait filtered down to the matching
+/// methods or an item given the `cfg` attributes of its enclosing module, are owned. Use
+/// [`to_owned`] to get the same results as [`select`].
+pub fn select_cow<'f>(path: &str, file: &'f syn::File) -> Result<Vec<Cow<'f, Item>>, Error> {
+    Ok(Selector::try_from(path)?.apply_to_borrowed(file))
+}
+
+/// Take ownership of results returned by [`select_cow`], cloning the borrowed ones.
+pub fn to_owned(results: Vec<Cow<'_, Item>>) -> Vec<Item> {
+    results.into_iter().map(Cow::into_owned).collect()
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified
+/// path, wrapping each one as a statement so it can be placed in a function body.
+pub fn select_stmts(path: &str, file: &syn::File) -> Result<Vec<Stmt>, Error> {
+    Ok(select(path, file)?.into_iter().map(Stmt::Item).collect())
+}
+
+/// Search a file for items with any of the given names, at any depth, in a single pass.
+///
+/// This matches the same things as [`Selector::ends_with`] would for each name, including
+/// members of traits and impls, enum variants and fields. Results that are identical to an
+/// earlier result are dropped, so an item is returned once even if it was found several ways.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} fn c() {} } struct D;").unwrap();
+/// let results = syn_select::select_names(&["b", "D", "e"], &file).unwrap();
+/// assert_eq!(results.len(), 2);
+/// ```
+pub fn select_names(names: &[&str], file: &syn::File) -> Result<Vec<Item>, Error> {
+    let mut results: Vec<Item> = Vec::new();
+    for item in Selector::any_of(names)?.apply_to(file) {
+        if !results.iter().any(|seen| same_item(seen, &item)) {
+            results.push(item);
+        }
+    }
+
+    Ok(results)
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// returning the generics of each result.
+///
+/// Items that can't have generics, such as modules and `use` items, contribute an empty
+/// `Generics`. For a container filtered down to some of its members, such as a trait narrowed
+/// to one method, these are the container's generics.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("struct A<T: Clone>(T); mod a {}").unwrap();
+/// let generics = syn_select::select_generics("_", &file).unwrap();
+/// assert_eq!(generics.len(), 2);
+/// assert_eq!(generics[0].params.len(), 1);
+/// assert!(generics[1].params.is_empty());
+/// ```
+pub fn select_generics(path: &str, file: &syn::File) -> Result<Vec<Generics>, Error> {
+    Ok(select(path, file)?.iter().map(generics_of).collect())
+}
+
+fn generics_of(item: &Item) -> Generics {
+    match item {
+        Item::Const(item) => item.generics.clone(),
+        Item::Enum(item) => item.generics.clone(),
+        Item::Fn(item) => item.sig.generics.clone(),
+        Item::Impl(item) => item.generics.clone(),
+        Item::Struct(item) => item.generics.clone(),
+        Item::Trait(item) => item.generics.clone(),
+        Item::TraitAlias(item) => item.generics.clone(),
+        Item::Type(item) => item.generics.clone(),
+        Item::Union(item) => item.generics.clone(),
+        _ => Generics::default(),
+    }
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// returning the signatures of the functions among them.
+///
+/// Trait and impl results contribute the signatures of their methods, so `C::d` returns the
+/// signature of the method `d` whether or not it has a default body. Results that aren't
+/// functions, traits or impls contribute nothing.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("trait C { fn d(&self) -> u8; } fn e() {}").unwrap();
+/// let signatures = syn_select::select_signatures("C::d", &file).unwrap();
+/// assert_eq!(signatures.len(), 1);
+/// assert_eq!(signatures[0].ident, "d");
+/// ```
+pub fn select_signatures(path: &str, file: &syn::File) -> Result<Vec<Signature>, Error> {
+    Ok(select(path, file)?.iter().flat_map(signatures_of).collect())
+}
+
+fn signatures_of(item: &Item) -> Vec<Signature> {
+    match item {
+        Item::Fn(item) => vec![item.sig.clone()],
+        Item::Trait(item) => item
+            .items
+            .iter()
+            .filter_map(|member| match member {
+                TraitItem::Fn(method) => Some(method.sig.clone()),
+                _ => None,
+            })
+            .collect(),
+        Item::Impl(item) => item
+            .items
+            .iter()
+            .filter_map(|member| match member {
+                ImplItem::Fn(method) => Some(method.sig.clone()),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parse a path, then search a file for newtypes that exactly match it, returning the type
+/// each one wraps, such as `f64` for `struct Meters(f64);`.
+///
+/// Only tuple structs with exactly one field are newtypes; other results contribute nothing.
+/// A path that selects fields, such as `Meters::0`, is checked against the whole struct, so
+/// `Pair::0` on `struct Pair(u8, u16);` returns nothing.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use quote::ToTokens;
+/// let file: syn::File = syn::parse_str("struct Meters(pub f64); struct Point(f64, f64);")
+///     .unwrap();
+/// let types = syn_select::select_newtype_inner("_", &file).unwrap();
+/// assert_eq!(types.len(), 1);
+/// assert_eq!(types[0].to_token_stream().to_string(), "f64");
+/// ```
+pub fn select_newtype_inner(path: &str, file: &syn::File) -> Result<Vec<Type>, Error> {
+    Ok(Selector::try_from(path)?
+        .stop_at_kind(ItemKind::Struct)
+        .apply_to(file)
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::Struct(ItemStruct {
+                fields: Fields::Unnamed(fields),
+                ..
+            }) if fields.unnamed.len() == 1 => {
+                fields.unnamed.into_iter().next().map(|field| field.ty)
+            }
+            _ => None,
+        })
+        .collect())
+}
+
+/// Parse a path, then search a file for trait members that exactly match it, returning them
+/// as impl members ready to be pasted into an `impl` block for the trait.
+///
+/// Methods get a body of `unimplemented!()`, replacing any default body. Constants and
+/// associated types keep their defaults, or are given `unimplemented!()` and `()`
+/// respectively. Results that aren't traits, and macros invoked inside traits, contribute
+/// nothing.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use quote::ToTokens;
+/// let file: syn::File = syn::parse_str("trait T { fn m(&self) -> u8; }").unwrap();
+/// let stubs = syn_select::select_as_impl_items("T::m", &file).unwrap();
+/// assert_eq!(
+///     stubs[0].to_token_stream().to_string(),
+///     "fn m (& self) -> u8 { unimplemented ! () }"
+/// );
+/// ```
+pub fn select_as_impl_items(path: &str, file: &syn::File) -> Result<Vec<ImplItem>, Error> {
+    Ok(select(path, file)?
+        .into_iter()
+        .flat_map(|item| match item {
+            Item::Trait(item) => item.items,
+            _ => Vec::new(),
+        })
+        .filter_map(impl_item_of)
+        .collect())
+}
+
+/// Convert a trait member to a stub implementing it.
+fn impl_item_of(member: TraitItem) -> Option<ImplItem> {
+    match member {
+        TraitItem::Const(item) => {
+            let (eq_token, expr) = item
+                .default
+                .unwrap_or_else(|| (Default::default(), parse_quote!(unimplemented!())));
+            Some(ImplItem::Const(ImplItemConst {
+                attrs: item.attrs,
+                vis: Visibility::Inherited,
+                defaultness: None,
+                const_token: item.const_token,
+                ident: item.ident,
+                generics: item.generics,
+                colon_token: item.colon_token,
+                ty: item.ty,
+                eq_token,
+                expr,
+                semi_token: item.semi_token,
+            }))
+        }
+        TraitItem::Fn(item) => Some(ImplItem::Fn(ImplItemFn {
+            attrs: item.attrs,
+            vis: Visibility::Inherited,
+            defaultness: None,
+            sig: item.sig,
+            block: parse_quote!({ unimplemented!() }),
+        })),
+        TraitItem::Type(item) => {
+            let (eq_token, ty) = item
+                .default
+                .unwrap_or_else(|| (Default::default(), parse_quote!(())));
+            Some(ImplItem::Type(ImplItemType {
+                attrs: item.attrs,
+                vis: Visibility::Inherited,
+                defaultness: None,
+                type_token: item.type_token,
+                ident: item.ident,
+                generics: item.generics,
+                eq_token,
+                ty,
+                semi_token: item.semi_token,
+            }))
+        }
+        _ => None,
+    }
+}
+
+/// Check whether two items are structurally identical, such as the same result returned
+/// by several searches.
+///
+/// Items are compared by their tokens, so differences in spans, whitespace and comments are
+/// ignored, but doc comments and attributes such as those added by the search are not.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+/// let first = syn_select::select("a::b", &file).unwrap();
+/// let second = syn_select::select("_::_fn", &file).unwrap();
+/// assert!(syn_select::same_item(&first[0], &second[0]));
+/// ```
+pub fn same_item(a: &Item, b: &Item) -> bool {
+    a.to_token_stream().to_string() == b.to_token_stream().to_string()
+}
+
+/// Parse a path, then search a file for results that exactly match the specified path,
+/// keeping at most one result of each [`ItemKind`].
+///
+/// When several results of the same kind match, the first one in document order is kept.
+/// For example, if a path matches a module and two functions declared under different `cfg`
+/// attributes, this returns the module and the first of the two functions.
+pub fn select_unique_kinds(path: &str, file: &syn::File) -> Result<Vec<Item>, Error> {
+    let mut seen = Vec::new();
+    Ok(select(path, file)?
+        .into_iter()
+        .filter(|item| {
+            let kind = ItemKind::of(item);
+            if seen.contains(&kind) {
+                false
+            } else {
+                seen.push(kind);
+                true
+            }
+        })
+        .collect())
+}
+
+/// Get every item of `kind` in a file, wherever it is declared.
+///
+/// This searches nested modules, function bodies, and the members of traits and impls, which
+/// are returned as free-standing items like the results of [`select`]. Results are in document
+/// order, with each container before the items inside it.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use syn_select::{select_all_of_kind, ItemKind};
+/// let file: syn::File = syn::parse_str(
+///     "struct A; mod b { struct C; fn d() { struct E; } }",
+/// ).unwrap();
+/// assert_eq!(select_all_of_kind(ItemKind::Struct, &file).len(), 3);
+/// ```
+pub fn select_all_of_kind(kind: ItemKind, file: &syn::File) -> Vec<Item> {
+    search::descendants(file)
+        .into_iter()
+        .filter(|item| ItemKind::of(item) == kind)
+        .map(Cow::into_owned)
+        .collect()
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// pairing each result with the names of its siblings.
+///
+/// The siblings of a result are the other named members of the container it was found in,
+/// which can help refine a query that matched more or less than expected. For a result that
+/// was filtered down to some of its members, such as a trait narrowed to one method, the
+/// siblings are the trait's other members.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} fn c() {} struct D; }").unwrap();
+/// let results = syn_select::select_with_sibling_names("a::b", &file).unwrap();
+/// assert_eq!(results.len(), 1);
+/// let siblings = results[0].1.iter().map(|ident| ident.to_string()).collect::<Vec<_>>();
+/// assert_eq!(siblings, vec!["c", "D"]);
+/// ```
+pub fn select_with_sibling_names(
+    path: &str,
+    file: &syn::File,
+) -> Result<Vec<(Item, Vec<Ident>)>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector).collect_sibling_names();
+    search.search_file(file);
+    Ok(search
+        .into_found_results()
+        .into_iter()
+        .map(|found| (found.item.into_owned(), found.siblings))
+        .collect())
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// returning each with whether it's a direct child of the file.
+///
+/// Results that aren't top-level were found inside another item, such as a module, a function
+/// body, or a trait or impl member, so they may be implementation details rather than part of
+/// the file's API. A trait or impl filtered down to the matching members is top-level if the
+/// trait or impl itself is.
+///
+/// Since `path` must start at the top of the file, this is most useful with paths containing
+/// wildcards; use [`Selector::apply_to_with_top_level`] with [`Selector::ends_with`] to tag
+/// results found at any depth.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("trait A { fn c(); } fn b() { trait A {} }").unwrap();
+/// let results = syn_select::select_with_top_level("**::A", &file).unwrap();
+/// let top_level = results.iter().map(|(_, top_level)| *top_level).collect::<Vec<_>>();
+/// assert_eq!(top_level, vec![true, false]);
+/// ```
+pub fn select_with_top_level(path: &str, file: &syn::File) -> Result<Vec<(Item, bool)>, Error> {
+    Ok(Selector::try_from(path)?.apply_to_with_top_level(file))
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// keeping the nesting of the items the search descended through to find them.
+///
+/// Each node's children are the results found inside its item, and the leaves are the items
+/// [`select`] would return. For example, `a::b::C::d` returns a node for `a` containing a node
+/// for `b`, which contains a node for the whole trait `C`, whose only child is the trait
+/// narrowed to `d`.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { mod b { fn c() {} } mod d { fn e() {} } }").unwrap();
+/// let tree = syn_select::select_tree("a::_::_", &file).unwrap();
+/// assert_eq!(tree.len(), 1);
+/// assert_eq!(tree[0].children.len(), 2);
+/// assert_eq!(tree[0].leaves().len(), 2);
+/// ```
+pub fn select_tree(path: &str, file: &syn::File) -> Result<Vec<MatchNode>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector).build_tree();
+    search.search_file(file);
+    Ok(search
+        .into_found_results()
+        .into_iter()
+        .map(MatchNode::from)
+        .collect())
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// returning the items that enclose the results instead of the results themselves.
+///
+/// For `a::b::C::d`, this returns `mod a`, `mod b` and the whole trait `C`. When there are
+/// several results, each container is returned once, outermost first and in document order.
+/// Like results, containers inherit the `cfg` attributes of the items around them.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { mod b { fn c() {} } }").unwrap();
+/// let ancestors = syn_select::select_ancestors("a::b::c", &file).unwrap();
+/// assert_eq!(ancestors.len(), 2);
+/// ```
+pub fn select_ancestors(path: &str, file: &syn::File) -> Result<Vec<Item>, Error> {
+    fn containers(nodes: Vec<MatchNode>, out: &mut Vec<Item>) {
+        for node in nodes {
+            if !node.children.is_empty() {
+                out.push(node.item);
+                containers(node.children, out);
+            }
+        }
+    }
+
+    let mut ancestors = Vec::new();
+    containers(select_tree(path, file)?, &mut ancestors);
+    Ok(ancestors)
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// keyed by the path at which each one was found.
+///
+/// # Key format
+/// Keys are the names of the items from the root of the file to the result, joined by `::`,
+/// such as `a::b::C`. A container filtered down to some of its members, such as a trait
+/// narrowed to one method, is keyed by the container's path.
+///
+/// When results of different [`ItemKind`]s share a path, each of their keys gets a `#` and
+/// the kind as a suffix, such as `a::b#mod` and `a::b#fn`. When results of the same kind share
+/// a path, such as two declarations under different `cfg` attributes, only the first is kept.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { mod b {} fn b() {} struct C; }").unwrap();
+/// let results = syn_select::select_map("a::_", &file).unwrap();
+/// let keys = results.keys().map(String::as_str).collect::<Vec<_>>();
+/// assert_eq!(keys, vec!["a::C", "a::b#fn", "a::b#mod"]);
+/// ```
+pub fn select_map(path: &str, file: &syn::File) -> Result<BTreeMap<String, Item>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector);
+    search.search_file(file);
+
+    let mut results = BTreeMap::new();
+    for (key, item) in keyed_by_path(search.into_found_results()) {
+        results.entry(key).or_insert(item);
+    }
+
+    Ok(results)
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified path,
+/// returning the path at which each one was found, in the same order as [`select`].
+///
+/// Paths are written like the keys of [`select_map`], so results of different kinds at the
+/// same path are told apart by a `#` and their kind, such as `a::b#mod` and `a::b#fn`. Unlike
+/// [`select_map`], results of the same kind at the same path each get an entry.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { mod b {} fn b() {} struct C; }").unwrap();
+/// let paths = syn_select::select_paths("a::_", &file).unwrap();
+/// assert_eq!(paths, vec!["a::b#mod", "a::b#fn", "a::C"]);
+/// ```
+pub fn select_paths(path: &str, file: &syn::File) -> Result<Vec<String>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector);
+    search.search_file(file);
+    Ok(keyed_by_path(search.into_found_results())
+        .into_iter()
+        .map(|(key, _)| key)
+        .collect())
+}
+
+/// Key each result by the path at which it was found, adding its kind when results of
+/// different kinds share the path. See [`select_map`] for the format.
+fn keyed_by_path(found: Vec<search::Found<'_>>) -> Vec<(String, Item)> {
+    let mut kinds = BTreeMap::<String, Vec<ItemKind>>::new();
+    let keyed = found
+        .into_iter()
+        .map(|found| {
+            let path = found.path.join("::");
+            let kind = ItemKind::of(&found.item);
+            let seen = kinds.entry(path.clone()).or_default();
+            if !seen.contains(&kind) {
+                seen.push(kind);
+            }
+            (path, kind, found.item.into_owned())
+        })
+        .collect::<Vec<_>>();
+
+    keyed
+        .into_iter()
+        .map(|(path, kind, item)| {
+            let key = if kinds[&path].len() > 1 {
+                format!("{}#{}", path, kind)
+            } else {
+                path
+            };
+            (key, item)
+        })
+        .collect()
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified
+/// path, and remove those results from the file.
+///
+/// This returns the same results as [`select`]. Results that were filtered down to some of
+/// their members, such as a trait narrowed to one method or a struct narrowed to one field,
+/// remove only those members and leave the rest of the item in place.
+///
+/// Items declared inside `let` initializers or in out-of-line modules loaded by a module
+/// resolver are returned but can't be removed.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use quote::ToTokens;
+/// let mut file: syn::File = syn::parse_str("mod a { fn b() {} fn c() {} }").unwrap();
+/// let removed = syn_select::select_remove("a::b", &mut file).unwrap();
+/// assert_eq!(removed.len(), 1);
+/// assert_eq!(file.to_token_stream().to_string(), "mod a { fn c () { } }");
+/// ```
+pub fn select_remove(path: &str, file: &mut syn::File) -> Result<Vec<Item>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector);
+    search.search_file(file);
+    Ok(remove::remove(file, search.into_found_results()))
+}
+
+/// Parse a path, then search a file for all results that exactly match the specified
+/// path, returning each result with the `cfg` predicates guarding it.
+///
+/// The predicates come from the result's own `#[cfg(...)]` attributes and those of the
+/// modules and other items it was found in, so a tool can report that a result is only
+/// available under `feature = "h"` without walking the file itself.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str(
+///     r#"#[cfg(feature = "g")] mod imp { #[cfg(not(test))] pub struct H; }"#,
+/// ).unwrap();
+/// let results = syn_select::select_with_cfgs("imp::H", &file).unwrap();
+/// let cfgs = results[0].cfgs.iter().map(ToString::to_string).collect::<Vec<_>>();
+/// assert_eq!(cfgs, vec![r#"feature = "g""#, "not(test)"]);
+/// ```
+pub fn select_with_cfgs(path: &str, file: &syn::File) -> Result<Vec<Match>, Error> {
+    Ok(Selector::try_from(path)?.apply_to_with_cfgs(file))
+}
+
+/// A search result paired with the byte range where it appears in the searched source.
+type ItemWithOffset = (Item, Option<Range<usize>>);
+
+/// Parse `src` as a file, then search it for all results that exactly match the specified
+/// path, returning each result with the byte range where it appears in `src`.
+///
+/// This does not require `proc-macro2`'s `span-locations` feature; instead, each result is
+/// rendered back to tokens and located by its first occurrence in `src`, ignoring whitespace
+/// and comments. Because of this:
+///
+/// 1. Identical items declared in several places will all report the offset of the first one.
+/// 2. The range excludes the item's outer attributes.
+/// 3. Results that were filtered by the search, such as a trait narrowed to a single method,
+///    don't appear verbatim in `src` and will have no range.
+pub fn select_with_offsets(path: &str, src: &str) -> Result<Vec<ItemWithOffset>, Error> {
+    let selector = Selector::try_from(path)?;
+    let file = syn::parse_file(src).map_err(Error::parse)?;
+    Ok(selector
+        .apply_to(&file)
+        .into_iter()
+        .map(|item| {
+            let range = offsets::find(&item, src);
+            (item, range)
+        })
+        .collect())
+}
+
+/// Parse `src` as a file, then search it for the results of the specified path that appear
+/// in the section of `src` following a line comment containing `marker`, such as
+/// `// MARK: internal`.
+///
+/// If `marker` contains a colon, the section ends at the next line comment that starts the
+/// same way up to that colon, such as `// MARK: public`; otherwise it runs to the end of the
+/// file. Results are located the way [`select_with_offsets`] locates them, so results it
+/// can't locate are never returned.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let src = "
+///     fn public() {}
+///     // MARK: internal
+///     fn helper() {}
+///     // MARK: tests
+///     fn check() {}
+/// ";
+/// let results = syn_select::select_after_comment("_fn", "MARK: internal", src).unwrap();
+/// assert_eq!(results.len(), 1);
+/// ```
+pub fn select_after_comment(path: &str, marker: &str, src: &str) -> Result<Vec<Item>, Error> {
+    let section = match offsets::comment_section(src, marker) {
+        Some(section) => section,
+        None => return Ok(Vec::new()),
+    };
+
+    Ok(select_with_offsets(path, src)?
+        .into_iter()
+        .filter_map(|(item, range)| match range {
+            Some(range) if section.contains(&range.start) => Some(item),
+            _ => None,
+        })
+        .collect())
+}
+
+/// Parse `src` as a file, search it for the specified path, and print the results as Rust
+/// source, for tools that show one item of a larger file, like `cargo expand` with a path.
+///
+/// Results carry the `cfg` attributes of the items they were found in, and are printed by
+/// [`render`], so they're only formatted like `rustfmt` output with the `prettyplease`
+/// feature. Source that isn't a valid Rust file is an [`ErrorKind::Parse`] error, and a path
+/// that matches nothing is an error explaining which segment failed, as from
+/// [`Selector::try_apply_to`].
+///
+/// # Usage
+/// ```rust,edition2018
+/// let src = "#[cfg(test)] mod tests { fn check() {} }";
+/// let output = syn_select::extract("tests::check", src).unwrap();
+/// assert!(output.contains("test"));
+/// assert!(syn_select::extract("tests::other", src).is_err());
+/// ```
+pub fn extract(path: &str, src: &str) -> Result<String, Error> {
+    let selector = Selector::try_from(path)?;
+    let file = syn::parse_file(src).map_err(Error::parse)?;
+    Ok(render(&selector.try_apply_to(&file)?))
+}
+
+/// Find the innermost item that covers `line` in a file parsed from source text, or `None`
+/// if no item covers it. Lines are counted from 1, as in editors.
+///
+/// When several nested items cover the line, such as a module, a function in it and a struct
+/// declared in the function's body, the most deeply nested one is returned. Members of traits
+/// and impls are returned as free-standing items, like functions for methods.
+///
+/// This requires the `span-locations` feature, and only works on files parsed outside of a
+/// procedural macro, such as with `syn::parse_file`.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file = syn::parse_file("mod a {\n    fn b() {}\n}\n").unwrap();
+/// let item = syn_select::select_at_line(2, &file).unwrap();
+/// assert!(matches!(item, syn::Item::Fn(item) if item.sig.ident == "b"));
+/// assert!(syn_select::select_at_line(4, &file).is_none());
+/// ```
+#[cfg(feature = "span-locations")]
+pub fn select_at_line(line: usize, file: &syn::File) -> Option<Item> {
+    use syn::spanned::Spanned;
+
+    // Containers come before the items inside them, so the last item covering the line is
+    // the innermost one.
+    search::descendants(file)
+        .into_iter()
+        .filter(|item| {
+            let span = item.span();
+            span.start().line <= line && line <= span.end().line
+        })
+        .last()
+        .map(Cow::into_owned)
+}
+
+/// Parse a path, then search a file for all results that exactly match it and lie within
+/// `lines`, such as the lines of a selection in an editor. Lines are counted from 1, and
+/// `lines.end` is excluded, so `3..5` covers lines 3 and 4.
+///
+/// A result lies within the range if it starts and ends inside it, ignoring its attributes,
+/// since the search may add attributes from enclosing items such as their `cfg`s.
+///
+/// This requires the `span-locations` feature, and only works on files parsed outside of a
+/// procedural macro, such as with `syn::parse_file`.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file = syn::parse_file("fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+/// let results = syn_select::select_in_range("_fn", &file, 2..4).unwrap();
+/// assert_eq!(results.len(), 2);
+/// ```
+#[cfg(feature = "span-locations")]
+pub fn select_in_range(
+    path: &str,
+    file: &syn::File,
+    lines: Range<usize>,
+) -> Result<Vec<Item>, Error> {
+    Ok(select(path, file)?
+        .into_iter()
+        .filter(|item| {
+            let span = search::span_without_attrs(item);
+            lines.contains(&span.start().line) && lines.contains(&span.end().line)
+        })
+        .collect())
+}
+
+/// Parse a path, then search a file for all results that exactly match it, returning each
+/// result with the span of the source it was found in, for editors and other tools that need
+/// to point at matches.
+///
+/// The span runs from the end of the result's attributes to its end, since the search may add
+/// attributes from enclosing items such as their `cfg`s. For a result filtered down to some of
+/// its members, such as a trait narrowed to one method, it's the span of the whole container.
+///
+/// Spans of files parsed with `syn::parse_file` only know their line and column with the
+/// `span-locations` feature; inside a procedural macro, they're the compiler's spans.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file = syn::parse_file("mod a {\n    fn b() {}\n}\n").unwrap();
+/// let results = syn_select::select_spanned("a::b", &file).unwrap();
+/// assert_eq!(results.len(), 1);
+/// ```
+pub fn select_spanned(path: &str, file: &syn::File) -> Result<Vec<(Item, Span)>, Error> {
+    let selector = Selector::try_from(path)?;
+    let mut search = Search::new(&selector);
+    search.search_file(file);
+    Ok(search.into_spanned_results())
+}
+
 #[cfg(test)]
 mod tests {
-    use syn::Item;
+    use quote::ToTokens;
+    use syn::{Item, TraitItem};
 
-    use super::{select, Selector};
+    use super::{
+        extract, outline, render, same_item, select, select_after_comment, select_all_of_kind,
+        select_ancestors, select_as_impl_items, select_cow, select_from_items, select_generics,
+        select_many, select_map, select_names, select_newtype_inner, select_outcome, select_paths,
+        select_remove, select_signatures, select_stmts, select_tree, select_unique_kinds,
+        select_with_cfgs, select_with_offsets, select_with_sibling_names, select_with_top_level,
+        to_owned, CfgPredicate, ErrorKind, ItemKey, ItemKind, MatchNode, SelectOutcome, Selector,
+    };
+    #[cfg(feature = "span-locations")]
+    use super::{select_at_line, select_in_range, select_spanned};
 
     fn sample() -> syn::File {
         syn::parse_str(
@@ -164,9 +964,3648 @@ mod tests {
         }
     }
 
+    /// Test that every module with a repeated name is searched, even without `cfg`
+    /// attributes to tell them apart, as in partially-edited code.
+    #[test]
+    fn duplicate_modules() {
+        let file: syn::File = syn::parse_str(
+            "mod imp {
+                struct First;
+                struct Shared(u8);
+            }
+            mod imp {
+                struct Second;
+                struct Shared(u16);
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(select("imp", &file).unwrap().len(), 2);
+        assert_eq!(select("imp::First", &file).unwrap().len(), 1);
+        assert_eq!(select("imp::Second", &file).unwrap().len(), 1);
+
+        let shared = select("imp::Shared", &file).unwrap();
+        assert_eq!(
+            shared
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect::<Vec<_>>(),
+            vec!["struct Shared (u8) ;", "struct Shared (u16) ;"]
+        );
+    }
+
     #[test]
     fn example_6() {
         let result = search_sample("a::b::C::_::E");
         assert_eq!(result.len(), 2);
     }
+
+    #[test]
+    fn offsets() {
+        let src = r#"
+            mod a {
+                /// A struct
+                #[derive(Debug)]
+                pub struct E {
+                    // Comments are ignored
+                    x: u8,
+                }
+
+                fn f() {
+                    let s = "/* not a comment */";
+                    struct E;
+                }
+            }"#;
+
+        let results = select_with_offsets("a::E", src).unwrap();
+        assert_eq!(results.len(), 1);
+        let range = results[0].1.clone().unwrap();
+        assert!(src[range.clone()].starts_with("pub struct E {"));
+        assert!(src[range].ends_with('}'));
+
+        let results = select_with_offsets("a::f::E", src).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(&src[results[0].1.clone().unwrap()], "struct E;");
+    }
+
+    #[test]
+    fn offsets_parse_error() {
+        match select_with_offsets("a", "mod a {") {
+            Err(error) => assert!(matches!(error.kind(), ErrorKind::Parse(_))),
+            Ok(_) => panic!("Source should not parse"),
+        }
+    }
+
+    #[test]
+    fn error_kind() {
+        let error = Selector::try_from("  ").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::EmptyPath));
+
+        let error = Selector::try_from("a::b-c").unwrap_err();
+        match error.kind() {
+            ErrorKind::InvalidSegment(segment) => assert_eq!(segment, "b-c"),
+            _ => panic!("Error was wrong kind"),
+        }
+
+        let error = Selector::try_from("a::fn").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidSegment(_)));
+        let detail = error.detail().unwrap();
+        assert!(detail.contains("keyword"), "{}", detail);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Invalid path segment: `fn` is not an identifier: {}",
+                detail
+            )
+        );
+
+        let error = Selector::try_from("_fn[1..x]").unwrap_err();
+        assert!(error.detail().is_none());
+    }
+
+    /// Members whose bodies declare items are still searched when members without any are
+    /// skipped.
+    #[test]
+    fn unanchored_member_bodies() {
+        let file: syn::File = syn::parse_str(
+            "impl S {
+                fn new() -> Self {
+                    struct InNew;
+                    S
+                }
+                fn get(&self) {}
+                const C: u8 = {
+                    struct InConst;
+                    1
+                };
+            }
+            trait T {
+                fn d() {
+                    unsafe {
+                        struct InDefault;
+                    }
+                }
+                fn e();
+            }",
+        )
+        .unwrap();
+        let find = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .anchored(false)
+                .apply_to(&file)
+                .len()
+        };
+
+        assert_eq!(find("InNew"), 1);
+        assert_eq!(find("new::InNew"), 1);
+        assert_eq!(find("InConst"), 1);
+        assert_eq!(find("InDefault"), 1);
+        assert_eq!(find("T::d::InDefault"), 1);
+        assert_eq!(find("get::_"), 0);
+    }
+
+    /// `*` matches exactly one level and `**` any number of levels, including none.
+    #[test]
+    fn globs() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                struct E;
+                mod b {
+                    struct E;
+                    mod c { struct E; }
+                }
+                trait T {
+                    fn f() { struct E; }
+                }
+            }
+            struct E;",
+        )
+        .unwrap();
+        let paths =
+            |path: &str| -> Vec<String> { select_map(path, &file).unwrap().into_keys().collect() };
+
+        assert_eq!(paths("a::*::E"), vec!["a::b::E"]);
+        assert_eq!(
+            paths("a::**::E"),
+            vec!["a::E", "a::T::f::E", "a::b::E", "a::b::c::E"]
+        );
+        assert_eq!(
+            paths("a::*::**::E"),
+            vec!["a::T::f::E", "a::b::E", "a::b::c::E"]
+        );
+        assert_eq!(
+            paths("a::**::*::E"),
+            vec!["a::T::f::E", "a::b::E", "a::b::c::E"]
+        );
+        assert_eq!(paths("a::**::c::E"), vec!["a::b::c::E"]);
+        assert_eq!(paths("**::T::f"), vec!["a::T"]);
+
+        // Items reached along more than one path are only returned once.
+        assert_eq!(select("**::E", &file).unwrap().len(), 5);
+        assert_eq!(select("**::**::E", &file).unwrap().len(), 5);
+
+        // Selectors display as written, so they survive a round trip through a string.
+        for path in &["a::*::E", "a::**::E", "*::_::*::E", "**::*_impl::*"] {
+            let selector = Selector::try_from(path).unwrap();
+            assert_eq!(selector.to_string(), *path);
+            assert_eq!(Selector::try_from(selector.to_string()).unwrap(), selector);
+        }
+        assert_eq!(paths("a::*::E"), paths("a::_::E"));
+
+        let error = Selector::try_from("a::**").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidSegment(_)));
+        match Selector::try_from("a::**::F").unwrap().try_apply_to(&file) {
+            Err(error) => assert!(
+                matches!(error.kind(), ErrorKind::LeafNotFound { position: 2, .. }),
+                "{}",
+                error
+            ),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn glob_segments() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                fn handle_get() {}
+                fn handle_post() {}
+                fn handle() {}
+                struct get_handler;
+                fn get_user_v2() {}
+                fn get_v2() {}
+                fn get_user_v3() {}
+            }
+            struct S { r#type: u8, typed: u8, other: u8 }",
+        )
+        .unwrap();
+        let names = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| match item {
+                    Item::Fn(item) => item.sig.ident.to_string(),
+                    Item::Struct(item) => item.ident.to_string(),
+                    _ => panic!("Result was wrong type"),
+                })
+                .collect()
+        };
+
+        assert_eq!(names("a::handle_*"), vec!["handle_get", "handle_post"]);
+        assert_eq!(names("a::*_handler"), vec!["get_handler"]);
+        assert_eq!(names("a::get_*_v2"), vec!["get_user_v2"]);
+        assert_eq!(
+            names("a::*get*"),
+            vec![
+                "handle_get",
+                "get_handler",
+                "get_user_v2",
+                "get_v2",
+                "get_user_v3"
+            ]
+        );
+        assert_eq!(
+            names("a::handle*"),
+            vec!["handle_get", "handle_post", "handle"]
+        );
+
+        match &select("S::typ*", &file).unwrap()[0] {
+            Item::Struct(item) => assert_eq!(item.fields.len(), 2),
+            _ => panic!("Result was wrong type"),
+        }
+
+        for path in &["a::handle_*", "*_handler", "get_*_v2"] {
+            let selector = Selector::try_from(path).unwrap();
+            assert_eq!(selector.to_string(), *path);
+            assert!(selector.has_wildcard());
+        }
+        assert!(Selector::try_from("a::handle-*").is_err());
+    }
+
+    /// `**` finds items at every depth, and repeating it doesn't search the same levels again.
+    #[test]
+    fn any_depth_nesting() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                struct E;
+                mod b {
+                    struct E;
+                    mod c {
+                        struct E;
+                        mod d { struct F; }
+                    }
+                }
+            }",
+        )
+        .unwrap();
+        let paths =
+            |path: &str| -> Vec<String> { select_map(path, &file).unwrap().into_keys().collect() };
+
+        assert_eq!(paths("a::**::E"), vec!["a::E", "a::b::E", "a::b::c::E"]);
+        assert_eq!(paths("a::**::**::E"), paths("a::**::E"));
+        assert_eq!(paths("a::**::**::**::d::F"), vec!["a::b::c::d::F"]);
+        assert_eq!(
+            Selector::try_from("a::**::**::E").unwrap().to_string(),
+            "a::**::**::E"
+        );
+
+        // Deep nesting with many `**` segments must not take exponentially long.
+        let mut src = "struct E;".to_string();
+        for _ in 0..40 {
+            src = format!("mod m {{ {} }}", src);
+        }
+        let deep: syn::File = syn::parse_str(&src).unwrap();
+        let results = select("**::**::**::**::**::**::E", &deep).unwrap();
+        assert_eq!(results.len(), 1);
+        let results = select("**::m::**::m::**::E", &deep).unwrap();
+        assert_eq!(results.len(), 1);
+    }
+
+    /// An item reached at the same `**` through several ancestors is only searched once, and
+    /// the results are those of the first visit.
+    #[test]
+    fn any_depth_revisits() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                mod b {
+                    mod c { struct E; }
+                    struct E;
+                }
+            }",
+        )
+        .unwrap();
+        let (results, diagnostics) = Selector::try_from("**::_::**::E")
+            .unwrap()
+            .apply_to_with_diagnostics(&file);
+        let paths = select_paths("**::_::**::E", &file).unwrap();
+        let skipped = diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic
+                    .message
+                    .ends_with("was already searched for `**`")
+            })
+            .count();
+
+        assert_eq!(results.len(), 2);
+        assert_eq!(paths, vec!["a::b::c::E", "a::b::E"]);
+        assert!(skipped > 0);
+
+        let tree = select_tree("**::_::**::E", &file).unwrap();
+        let leaves = tree.iter().flat_map(MatchNode::leaves).count();
+        assert_eq!(leaves, 2);
+    }
+
+    #[test]
+    fn newtype_inner() {
+        let file: syn::File = syn::parse_str(
+            "struct Meters(pub f64);
+            struct Wrapper<T>(Vec<T>);
+            struct Pair(u8, u16);
+            struct Named { x: u8 }
+            struct Unit;
+            enum E { A(u8) }",
+        )
+        .unwrap();
+        let inner = |path: &str| -> Vec<String> {
+            select_newtype_inner(path, &file)
+                .unwrap()
+                .iter()
+                .map(|ty| ty.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(inner("_"), vec!["f64", "Vec < T >"]);
+        assert_eq!(inner("Meters"), vec!["f64"]);
+        assert!(inner("Pair").is_empty());
+        assert!(inner("Pair::0").is_empty());
+        assert!(inner("Pair::1").is_empty());
+        assert_eq!(inner("Meters::0"), vec!["f64"]);
+        assert_eq!(inner("_::0"), vec!["f64", "Vec < T >"]);
+        assert!(inner("Named").is_empty());
+        assert!(inner("Named::x").is_empty());
+        assert!(inner("E").is_empty());
+    }
+
+    #[test]
+    fn outcome() {
+        match select_outcome("a::b", &sample()).unwrap() {
+            SelectOutcome::Found(items) => assert_eq!(items.len(), 2),
+            SelectOutcome::NotFound => panic!("Expected results"),
+        }
+
+        let outcome = select_outcome("a::x", &sample()).unwrap();
+        assert!(matches!(outcome, SelectOutcome::NotFound));
+        assert!(!outcome.is_found());
+        assert!(outcome.into_items().is_empty());
+
+        assert!(select_outcome("a::b-c", &sample()).is_err());
+    }
+
+    #[test]
+    fn expect_kind() {
+        let selector = Selector::try_from("a::b").unwrap();
+        match selector.expect_kind(&sample(), ItemKind::Fn) {
+            Ok(Item::Fn(item)) => assert_eq!(item.sig.ident, "b"),
+            _ => panic!("Expected the fn `b`"),
+        }
+        assert!(matches!(
+            selector.expect_kind(&sample(), ItemKind::Mod),
+            Ok(Item::Mod(_))
+        ));
+
+        let error = match selector.expect_kind(&sample(), ItemKind::Struct) {
+            Err(error) => error,
+            Ok(_) => panic!("Expected an error"),
+        };
+        assert!(matches!(
+            error.kind(),
+            ErrorKind::KindMismatch {
+                expected: ItemKind::Struct,
+                found: ItemKind::Mod,
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Expected a result of kind `struct`, but found `mod`"
+        );
+
+        match Selector::try_from("a::x")
+            .unwrap()
+            .expect_kind(&sample(), ItemKind::Fn)
+        {
+            Err(error) => assert!(matches!(error.kind(), ErrorKind::LeafNotFound { .. })),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn separators() {
+        for (path, position) in &[("a::::b", 1), ("::a", 0), ("a::", 1), ("a::b::", 2)] {
+            match Selector::try_from(path).unwrap_err().kind() {
+                ErrorKind::EmptySegment(pos) => assert_eq!(pos, position, "{}", path),
+                _ => panic!("Error was wrong kind for {}", path),
+            }
+        }
+
+        let error = Selector::try_from("a:::b").unwrap_err();
+        match error.kind() {
+            ErrorKind::InvalidSegment(segment) => assert_eq!(segment, ":b"),
+            _ => panic!("Error was wrong kind"),
+        }
+        assert!(error.to_string().contains("stray `:`"));
+
+        for path in &["a:b", "`a::b`", "\"a::b\""] {
+            assert!(Selector::try_from(path).is_err(), "{}", path);
+        }
+
+        let selector = Selector::try_from("r#type::r#fn").unwrap();
+        assert_eq!(selector.to_string(), "r#type::r#fn");
+        let file: syn::File = syn::parse_str("mod r#type { fn r#fn() {} }").unwrap();
+        assert_eq!(selector.apply_to(&file).len(), 1);
+    }
+
+    #[test]
+    fn mod_resolver() {
+        let file: syn::File = syn::parse_str("mod a { mod b; } mod c;").unwrap();
+        let selector = Selector::try_from("a::b::D")
+            .unwrap()
+            .with_mod_resolver(|path| {
+                if path == ["a", "b"] {
+                    Some(syn::parse_str("struct D;").unwrap())
+                } else {
+                    None
+                }
+            });
+
+        let result = selector.apply_to(&file);
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.ident, ident("D"));
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn mod_path_resolver() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(unix)]
+            #[path = "imp/unix.rs"]
+            mod imp;
+            #[cfg(windows)]
+            #[path = "imp/windows.rs"]
+            mod imp;
+            mod other;
+            "#,
+        )
+        .unwrap();
+        let selector = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .with_mod_path_resolver(|path, file_path| match (path, file_path) {
+                    (_, Some("imp/unix.rs")) => syn::parse_str("struct Unix;").ok(),
+                    (_, Some("imp/windows.rs")) => syn::parse_str("struct Windows;").ok(),
+                    ([name], None) if name == "other" => syn::parse_str("struct Other;").ok(),
+                    _ => None,
+                })
+        };
+
+        assert_eq!(selector("imp::_").apply_to(&file).len(), 2);
+        assert_eq!(selector("imp::Unix").apply_to(&file).len(), 1);
+        assert_eq!(selector("imp::Windows").apply_to(&file).len(), 1);
+        assert_eq!(selector("other::Other").apply_to(&file).len(), 1);
+    }
+
+    /// A module whose `#[path]` leads back to a file that's already being searched must not
+    /// be followed forever, while distinct modules with the same contents are all searched.
+    #[test]
+    fn mod_resolver_cycle() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let file: syn::File = syn::parse_str(r#"#[path = "a.rs"] mod a;"#).unwrap();
+        let selector = |path: &str| {
+            let counter = counter.clone();
+            Selector::try_from(path)
+                .unwrap()
+                .with_mod_path_resolver(move |_, file_path| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    match file_path {
+                        Some("a.rs") => syn::parse_str(r#"#[path = "a.rs"] mod a; struct E;"#).ok(),
+                        _ => None,
+                    }
+                })
+        };
+
+        assert_eq!(selector("a::E").apply_to(&file).len(), 1);
+        assert!(selector("a::a::a::a::E").apply_to(&file).is_empty());
+        assert_eq!(selector("**::E").apply_to(&file).len(), 1);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        let file: syn::File = syn::parse_str("mod x; mod y; mod z { mod x; }").unwrap();
+        let results = Selector::try_from("**::E")
+            .unwrap()
+            .with_mod_resolver(|_| syn::parse_str("struct E;").ok())
+            .apply_to(&file);
+        assert_eq!(results.len(), 3);
+    }
+
+    /// A loader passed to one search can keep state, and is used for nested and circular
+    /// module declarations like a registered resolver.
+    #[test]
+    fn loader() {
+        let file: syn::File = syn::parse_str(r#"mod a; #[path = "cycle.rs"] mod cycle;"#).unwrap();
+        let mut calls = Vec::new();
+        let mut load = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .apply_to_with_loader(&file, |path, _| {
+                    calls.push(path.join("::"));
+                    match path.last().map(String::as_str) {
+                        Some("a") => syn::parse_str("mod b; struct E;").ok(),
+                        Some("b") => syn::parse_str("struct E;").ok(),
+                        Some("cycle") => {
+                            syn::parse_str(r#"#[path = "cycle.rs"] mod cycle; struct F;"#).ok()
+                        }
+                        _ => None,
+                    }
+                })
+                .len()
+        };
+
+        assert_eq!(load("a::b::E"), 1);
+        assert_eq!(load("cycle::cycle::cycle::F"), 0);
+        assert_eq!(calls, vec!["a", "a::b", "cycle"]);
+
+        let with_path: syn::File =
+            syn::parse_str(r#"mod outer { #[path = "imp/unix.rs"] mod imp; mod plain; }"#).unwrap();
+        let mut calls = Vec::new();
+        let results = Selector::try_from("outer::imp::inner::E")
+            .unwrap()
+            .apply_to_with_loader(&with_path, |path, file_path| {
+                calls.push((path.join("::"), file_path.map(String::from)));
+                match path.last().map(String::as_str) {
+                    Some("imp") => syn::parse_str("mod inner;").ok(),
+                    Some("inner") => syn::parse_str("struct E;").ok(),
+                    _ => None,
+                }
+            });
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            calls,
+            vec![
+                ("outer::imp".to_string(), Some("imp/unix.rs".to_string())),
+                ("outer::imp::inner".to_string(), None),
+            ]
+        );
+
+        let registered = Selector::try_from("a::E")
+            .unwrap()
+            .with_mod_resolver(|_| syn::parse_str("struct F;").ok());
+        let results =
+            registered.apply_to_with_loader(&file, |_, _| syn::parse_str("struct E;").ok());
+        assert_eq!(results.len(), 1);
+    }
+
+    fn sample_enum() -> syn::File {
+        syn::parse_str(
+            "enum E {
+                A,
+                B(u8, u16),
+                C { x: u32, y: u64 },
+            }",
+        )
+        .unwrap()
+    }
+
+    /// Selecting a variant returns the enum rebuilt with only that variant, whatever its kind.
+    #[test]
+    fn variant_kinds() {
+        for (path, expected) in &[
+            ("E::A", "enum E { A }"),
+            ("E::B", "enum E { B (u8 , u16) }"),
+            ("E::C", "enum E { C { x : u32 , y : u64 } }"),
+        ] {
+            let result = select(path, &sample_enum()).unwrap();
+            assert_eq!(result.len(), 1);
+            match &result[0] {
+                Item::Enum(item) => assert_eq!(item.variants.len(), 1),
+                _ => panic!("Result was wrong type"),
+            }
+            assert_eq!(result[0].to_token_stream().to_string(), *expected);
+        }
+
+        assert!(select("E::D", &sample_enum()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn variant_tuple_field() {
+        let result = select("E::B::1", &sample_enum()).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Enum(item) = &result[0] {
+            assert_eq!(item.variants.len(), 1);
+            assert_eq!(item.variants[0].ident, ident("B"));
+            assert_eq!(item.variants[0].fields.len(), 1);
+            let ty = &item.variants[0].fields.iter().next().unwrap().ty;
+            assert_eq!(ty.to_token_stream().to_string(), "u16");
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn variant_named_field() {
+        let result = select("E::C::y", &sample_enum()).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Enum(item) = &result[0] {
+            assert_eq!(item.variants.len(), 1);
+            let fields = &item.variants[0].fields;
+            assert_eq!(fields.len(), 1);
+            assert_eq!(fields.iter().next().unwrap().ident, Some(ident("y")));
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn variant_field_missing() {
+        assert!(select("E::A::0", &sample_enum()).unwrap().is_empty());
+        assert!(select("E::B::2", &sample_enum()).unwrap().is_empty());
+        assert!(select("E::C::0", &sample_enum()).unwrap().is_empty());
+        assert!(select("E::C::x::y", &sample_enum()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn wildcard_range() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                struct B;
+                fn c() {}
+                enum D {}
+                trait E {}
+            }",
+        )
+        .unwrap();
+
+        let names = |path: &str| {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| {
+                    item.to_token_stream()
+                        .into_iter()
+                        .nth(1)
+                        .unwrap()
+                        .to_string()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("a::_[0..2]"), vec!["B", "c"]);
+        assert_eq!(names("a::_[..1]"), vec!["B"]);
+        assert_eq!(names("a::_[2..]"), vec!["D", "E"]);
+        assert_eq!(names("a::_[3..100]"), vec!["E"]);
+        assert!(names("a::_[10..20]").is_empty());
+
+        // Positions count items of every kind, not just those of the wildcard's kind.
+        assert_eq!(names("a::_fn[0..3]"), vec!["c"]);
+        assert!(names("a::_enum[0..2]").is_empty());
+        assert_eq!(names("a::_enum[2..3]"), vec!["D"]);
+    }
+
+    #[test]
+    fn top_level() {
+        let file: syn::File = syn::parse_str(
+            "struct A;
+            impl A { fn new() -> Self { struct A; A } }
+            mod m { struct A; }",
+        )
+        .unwrap();
+        let tags = |path: &str| -> Vec<(String, bool)> {
+            Selector::ends_with(path)
+                .unwrap()
+                .apply_to_with_top_level(&file)
+                .into_iter()
+                .map(|(item, top_level)| (ItemKind::of(&item).to_string(), top_level))
+                .collect()
+        };
+
+        assert_eq!(
+            tags("A"),
+            vec![
+                ("struct".to_string(), true),
+                ("impl".to_string(), true),
+                ("struct".to_string(), false),
+                ("struct".to_string(), false),
+            ]
+        );
+        assert_eq!(tags("A::new"), vec![("impl".to_string(), true)]);
+        assert!(!select_with_top_level("m::A", &file).unwrap()[0].1);
+    }
+
+    #[test]
+    fn reexports() {
+        let file: syn::File = syn::parse_str(
+            "pub use foo::Bar;
+            pub use foo::{inner::{self, Baz as Renamed}, Trait as _, glob::*};
+            use foo::Private;
+            pub(crate) use foo::Crate;
+            mod a { pub use super::Bar; }",
+        )
+        .unwrap();
+        let count = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .match_reexports(true)
+                .apply_to(&file)
+                .len()
+        };
+
+        let results = Selector::try_from("Bar")
+            .unwrap()
+            .match_reexports(true)
+            .apply_to(&file);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            results[0].to_token_stream().to_string(),
+            "pub use foo :: Bar ;"
+        );
+
+        assert_eq!(count("inner"), 1);
+        assert_eq!(count("Renamed"), 1);
+        assert_eq!(count("Baz"), 0);
+        assert_eq!(count("_use"), 4);
+        assert_eq!(count("Private"), 0);
+        assert_eq!(count("Crate"), 0);
+        assert_eq!(count("a::Bar"), 1);
+        assert_eq!(count("a::_use"), 1);
+        assert!(select("Bar", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn reexport_cfgs() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "x")]
+            mod m {
+                #[serde(rename = "b")]
+                #[cfg(unix)]
+                pub use a::B;
+            }"#,
+        )
+        .unwrap();
+
+        let result = Selector::try_from("m::B")
+            .unwrap()
+            .match_reexports(true)
+            .apply_to(&file);
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            r#"# [cfg (feature = "x")] # [serde (rename = "b")] # [cfg (unix)] pub use a :: B ;"#
+        );
+
+        assert_eq!(
+            Selector::try_from("m::B")
+                .unwrap()
+                .match_reexports(true)
+                .with_attr_arg("serde::rename", "b")
+                .apply_to(&file)
+                .len(),
+            1
+        );
+
+        let cfgs = select_with_cfgs("m::_use", &file).unwrap()[0]
+            .cfgs
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(cfgs, vec![r#"feature = "x""#, "unix"]);
+    }
+
+    #[test]
+    fn import_cfgs() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "x")]
+            mod m {
+                use a::B;
+            }"#,
+        )
+        .unwrap();
+
+        let result = Selector::try_from("m::B")
+            .unwrap()
+            .match_imports(true)
+            .apply_to(&file);
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            r#"# [cfg (feature = "x")] use a :: B ;"#
+        );
+    }
+
+    #[test]
+    fn imports() {
+        let file: syn::File = syn::parse_str(
+            "use a::b::C;
+            use a::b::C as D;
+            use a::{B, C};
+            use a::glob::*;
+            mod m { use super::C; }",
+        )
+        .unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            Selector::try_from(path)
+                .unwrap()
+                .match_imports(true)
+                .apply_to(&file)
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            rendered("C"),
+            vec!["use a :: b :: C ;", "use a :: { B , C } ;"]
+        );
+        assert_eq!(rendered("D"), vec!["use a :: b :: C as D ;"]);
+        assert_eq!(rendered("B"), vec!["use a :: { B , C } ;"]);
+        assert!(rendered("glob").is_empty());
+        assert_eq!(rendered("_use").len(), 4);
+        assert!(rendered("_use").contains(&"use a :: glob :: * ;".to_string()));
+        assert_eq!(rendered("m::C"), vec!["use super :: C ;"]);
+        assert_eq!(
+            Selector::try_from("C")
+                .unwrap()
+                .match_reexports(true)
+                .apply_to(&file)
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn into_string() {
+        let selector = Selector::try_from("a::_fn[..2]::b").unwrap();
+        assert_eq!(String::from(&selector), "a::_fn[..2]::b");
+
+        let path: String = selector.into();
+        assert_eq!(Selector::try_from(path.as_str()).unwrap().to_string(), path);
+    }
+
+    #[test]
+    fn wildcard_range_display() {
+        for path in &["a::_[0..10]", "a::_[..10]", "a::_[3..]", "_[..]::b"] {
+            assert_eq!(Selector::try_from(path).unwrap().to_string(), *path);
+        }
+
+        assert!(Selector::try_from("a::_[0]").is_err());
+        assert!(Selector::try_from("a::_[x..]").is_err());
+    }
+
+    /// Impl blocks are matched by the name of their self type, whether or not that type
+    /// is declared in the searched file.
+    #[test]
+    fn impl_without_type_definition() {
+        let file: syn::File = syn::parse_str(
+            "impl MyType {
+                fn method(&self) {
+                    struct Inner;
+                }
+                fn other() {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("MyType", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0], Item::Impl(_)));
+
+        let result = select("MyType::method", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Impl(item) = &result[0] {
+            assert_eq!(item.items.len(), 1);
+            if let syn::ImplItem::Fn(item) = &item.items[0] {
+                assert_eq!(item.sig.ident, ident("method"));
+            } else {
+                panic!("Impl item was wrong type");
+            }
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        let result = select("MyType::method::Inner", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0], Item::Struct(_)));
+    }
+
+    /// A search can be driven by a caller's own traversal instead of a whole file.
+    #[test]
+    fn embedded_search() {
+        use syn::visit::Visit;
+
+        struct ModVisitor<'a> {
+            search: super::Search<'a>,
+        }
+
+        impl<'a, 'ast> Visit<'ast> for ModVisitor<'a> {
+            fn visit_item_mod(&mut self, item: &'ast syn::ItemMod) {
+                if let Some((_, items)) = &item.content {
+                    self.search.search_items(items);
+                }
+                syn::visit::visit_item_mod(self, item);
+            }
+        }
+
+        let selector = Selector::try_from("C::d").unwrap();
+        let mut visitor = ModVisitor {
+            search: super::Search::new(&selector),
+        };
+        visitor.visit_file(&sample());
+
+        let results = visitor.search.into_results();
+        assert_eq!(results.len(), 1);
+        assert!(matches!(results[0], Item::Trait(_)));
+    }
+
+    #[test]
+    fn struct_named_field() {
+        let file: syn::File = syn::parse_str("struct S { a: u8, b: u16 }").unwrap();
+        let result = select("S::b", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.fields.len(), 1);
+            let field = item.fields.iter().next().unwrap();
+            assert_eq!(field.ident, Some(ident("b")));
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        assert!(select("S::c", &file).unwrap().is_empty());
+        assert!(select("S::0", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn struct_tuple_field() {
+        let file: syn::File = syn::parse_str("struct S(u8, u16);").unwrap();
+        let result = select("S::1", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.fields.len(), 1);
+            let field = item.fields.iter().next().unwrap();
+            assert_eq!(field.ty.to_token_stream().to_string(), "u16");
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        assert!(select("S::2", &file).unwrap().is_empty());
+
+        let error = Selector::try_from("S::01").unwrap_err();
+        assert!(matches!(error.kind(), ErrorKind::InvalidSegment(_)));
+        assert_eq!(
+            error.to_string(),
+            "Invalid path segment: `01` is not a field index; indices are written without leading zeros"
+        );
+    }
+
+    /// Items inside trait and impl members get the `cfg` attributes of the trait or impl, as
+    /// well as those of the member.
+    #[test]
+    fn member_body_cfg() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "x")]
+            trait T {
+                #[cfg(unix)]
+                fn f() { struct S; }
+            }
+            #[cfg(feature = "y")]
+            impl Foo {
+                fn g() { struct S; }
+            }"#,
+        )
+        .unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            rendered("T::f::S"),
+            vec![r#"# [cfg (feature = "x")] # [cfg (unix)] struct S ;"#]
+        );
+        assert_eq!(
+            rendered("Foo::g::S"),
+            vec![r#"# [cfg (feature = "y")] struct S ;"#]
+        );
+        assert_eq!(rendered("**::S"), rendered("_::_::S"));
+        assert_eq!(
+            rendered("T::f"),
+            vec![r#"# [cfg (feature = "x")] trait T { # [cfg (unix)] fn f () { struct S ; } }"#]
+        );
+        assert_eq!(
+            to_owned(Selector::ends_with("S").unwrap().apply_to_borrowed(&file))
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect::<Vec<_>>(),
+            rendered("_::_::S")
+        );
+    }
+
+    #[test]
+    fn bodyless_trait_method() {
+        let file: syn::File =
+            syn::parse_str("trait T { fn f(&self); fn g() { struct S; } }").unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(rendered("T::f"), vec!["trait T { fn f (& self) ; }"]);
+        assert!(rendered("T::f::S").is_empty());
+        assert!(rendered("T::f::_").is_empty());
+        assert_eq!(rendered("T::g::S"), vec!["struct S ;"]);
+
+        assert!(select("T::f::S::x", &file).unwrap().is_empty());
+        assert!(Selector::try_from("T::f::S")
+            .unwrap()
+            .try_apply_to(&file)
+            .is_err());
+
+        // Listing free-standing items doesn't invent a body for the method.
+        let functions = select_all_of_kind(ItemKind::Fn, &file)
+            .iter()
+            .map(|item| item.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(functions, vec!["fn g () { struct S ; }"]);
+        assert_eq!(outline(&file).children[0].children.len(), 1);
+    }
+
+    /// A field gated by `cfg` keeps its own attribute, while the `cfg` of the enclosing module
+    /// is merged onto the filtered struct ahead of the struct's own attributes.
+    #[test]
+    fn struct_field_cfg() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "g")]
+            mod m {
+                /// Docs
+                #[cfg(feature = "s")]
+                struct S {
+                    #[cfg(feature = "x")]
+                    a: u8,
+                    b: u8,
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let result = select("m::S::a", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            let attrs = item
+                .attrs
+                .iter()
+                .map(|attr| attr.to_token_stream().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                attrs,
+                vec![
+                    "# [doc = \" Docs\"]",
+                    "# [cfg (feature = \"g\")]",
+                    "# [cfg (feature = \"s\")]"
+                ]
+            );
+
+            assert_eq!(item.fields.len(), 1);
+            let field = item.fields.iter().next().unwrap();
+            assert_eq!(field.attrs.len(), 1);
+            assert_eq!(
+                field.attrs[0].to_token_stream().to_string(),
+                "# [cfg (feature = \"x\")]"
+            );
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn search_locals() {
+        let file: syn::File = syn::parse_str(
+            "fn my_fn() {
+                let f = || {
+                    struct Inner;
+                };
+                let Some(x) = y else {
+                    struct Other;
+                    return;
+                };
+            }",
+        )
+        .unwrap();
+
+        assert!(select("my_fn::Inner", &file).unwrap().is_empty());
+
+        let selector = Selector::try_from("my_fn::Inner")
+            .unwrap()
+            .search_locals(true);
+        assert_eq!(selector.apply_to(&file).len(), 1);
+
+        let selector = Selector::try_from("my_fn::Other")
+            .unwrap()
+            .search_locals(true);
+        assert_eq!(selector.apply_to(&file).len(), 1);
+    }
+
+    #[test]
+    fn ends_with() {
+        let selector = Selector::ends_with("E").unwrap();
+        let result = selector.apply_to(&sample());
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|item| matches!(item, Item::Struct(_))));
+
+        let selector = Selector::ends_with("C::f").unwrap();
+        let result = selector.apply_to(&sample());
+        assert_eq!(result.len(), 1);
+        assert!(matches!(result[0], Item::Trait(_)));
+
+        let selector = Selector::ends_with("b").unwrap();
+        assert_eq!(selector.apply_to(&sample()).len(), 2);
+    }
+
+    #[test]
+    fn unanchored_in_impl() {
+        let file: syn::File = syn::parse_str(
+            "impl A {
+                fn f() {
+                    mod b {
+                        struct C;
+                    }
+                }
+            }",
+        )
+        .unwrap();
+
+        assert!(select("b::C", &file).unwrap().is_empty());
+        let selector = Selector::try_from("b::C").unwrap().anchored(false);
+        assert_eq!(selector.apply_to(&file).len(), 1);
+    }
+
+    /// Filtered containers keep their own visibility so that they round-trip as valid code.
+    #[test]
+    fn filtered_container_visibility() {
+        let file: syn::File = syn::parse_str(
+            "pub(crate) struct S {
+                pub a: u8,
+                b: u8,
+            }
+
+            pub(super) enum E {
+                A,
+                B,
+            }
+
+            pub trait T {
+                fn f();
+                fn g();
+            }",
+        )
+        .unwrap();
+
+        let vis = |path: &str| {
+            let result = select(path, &file).unwrap();
+            assert_eq!(result.len(), 1);
+            match &result[0] {
+                Item::Struct(item) => item.vis.to_token_stream().to_string(),
+                Item::Enum(item) => item.vis.to_token_stream().to_string(),
+                Item::Trait(item) => item.vis.to_token_stream().to_string(),
+                _ => panic!("Result was wrong type"),
+            }
+        };
+
+        assert_eq!(vis("S::a"), "pub (crate)");
+        assert_eq!(vis("E::B"), "pub (super)");
+        assert_eq!(vis("T::g"), "pub");
+    }
+
+    /// Impls for different instantiations of a generic type are matched by the base type's
+    /// name, and each result keeps its own self type.
+    #[test]
+    fn impl_generic_instantiations() {
+        let file: syn::File = syn::parse_str(
+            "impl Foo<u8> {
+                fn f() {}
+            }
+
+            impl Foo<u16> {
+                fn f() {}
+                fn g() {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("Foo::f", &file).unwrap();
+        let self_tys = result
+            .iter()
+            .map(|item| match item {
+                Item::Impl(item) => {
+                    assert_eq!(item.items.len(), 1);
+                    item.self_ty.to_token_stream().to_string()
+                }
+                _ => panic!("Result was wrong type"),
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(self_tys, vec!["Foo < u8 >", "Foo < u16 >"]);
+    }
+
+    /// Inner attributes stay on a matched module, and an inner `cfg` is applied to the
+    /// module's descendants as an outer attribute.
+    #[test]
+    fn mod_inner_attrs() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "a")]
+            mod m {
+                #![cfg(feature = "b")]
+                #![allow(dead_code)]
+
+                struct S;
+            }"#,
+        )
+        .unwrap();
+
+        let result = select("m", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Mod(item) = &result[0] {
+            let inner = item
+                .attrs
+                .iter()
+                .filter(|attr| matches!(attr.style, syn::AttrStyle::Inner(_)))
+                .count();
+            assert_eq!(inner, 2);
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        let result = select("m::S", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            let attrs = item
+                .attrs
+                .iter()
+                .map(|attr| attr.to_token_stream().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(
+                attrs,
+                vec!["# [cfg (feature = \"a\")]", "# [cfg (feature = \"b\")]"]
+            );
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn unique_kinds() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            mod a {}
+            #[cfg(feature = "x")]
+            fn a() -> u8 { 1 }
+            #[cfg(not(feature = "x"))]
+            fn a() -> u8 { 2 }"#,
+        )
+        .unwrap();
+
+        assert_eq!(select("a", &file).unwrap().len(), 3);
+
+        let result = select_unique_kinds("a", &file).unwrap();
+        let kinds = result.iter().map(ItemKind::of).collect::<Vec<_>>();
+        assert_eq!(kinds, vec![ItemKind::Mod, ItemKind::Fn]);
+        if let Item::Fn(item) = &result[1] {
+            assert!(item.attrs[0]
+                .to_token_stream()
+                .to_string()
+                .contains("\"x\""));
+            assert!(!item.attrs[0].to_token_stream().to_string().contains("not"));
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    /// Impls for types without a simple name, like tuples and references, are matched by
+    /// the name of the trait they implement.
+    #[test]
+    fn impl_non_path_self_type() {
+        let file: syn::File = syn::parse_str(
+            "impl Display for (A, B) {
+                fn fmt() {}
+            }
+
+            impl<'a, T> Display for &'a T {
+                fn fmt() {}
+            }
+
+            impl Display for Foo {
+                fn fmt() {}
+            }
+
+            impl [u8] {
+                fn len() {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("Display::fmt", &file).unwrap();
+        let self_tys = result
+            .iter()
+            .map(|item| match item {
+                Item::Impl(item) => item.self_ty.to_token_stream().to_string(),
+                _ => panic!("Result was wrong type"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(self_tys, vec!["(A , B)", "& 'a T"]);
+
+        assert_eq!(select("Foo::fmt", &file).unwrap().len(), 1);
+        assert_eq!(select("_::fmt", &file).unwrap().len(), 3);
+        assert!(select("_::len", &file).unwrap().is_empty());
+    }
+
+    /// The `cfg` attributes of a function are merged onto items declared inside it,
+    /// after the item's docs and before its other attributes, as in `example_5`.
+    #[test]
+    fn fn_with_cfg_containing_struct() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(feature = "g")]
+            fn f() {
+                /// Documentation
+                #[derive(Debug)]
+                #[cfg(feature = "h")]
+                struct S;
+            }"#,
+        )
+        .unwrap();
+
+        let result = select("f::S", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.attrs.len(), 4);
+            assert!(item.attrs[0].path().is_ident("doc"));
+            assert!(item.attrs[1].path().is_ident("cfg"));
+            assert!(item.attrs[1]
+                .to_token_stream()
+                .to_string()
+                .contains("\"g\""));
+            assert!(item.attrs[2].path().is_ident("derive"));
+            assert!(item.attrs[3].path().is_ident("cfg"));
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn typed_wildcard() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                struct B;
+                fn c() {}
+                fn d() {}
+                enum E { F }
+                trait G {
+                    fn h();
+                    const I: u8;
+                }
+                fn _fn() {}
+            }",
+        )
+        .unwrap();
+
+        let kinds = |path: &str| {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(ItemKind::of)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(kinds("a::_fn"), vec![ItemKind::Fn; 3]);
+        assert_eq!(kinds("a::_struct"), vec![ItemKind::Struct]);
+        assert_eq!(kinds("a::_fn[1..2]"), vec![ItemKind::Fn]);
+        assert!(kinds("a::_enum::_fn").is_empty());
+        assert_eq!(kinds("a::_enum::_"), vec![ItemKind::Enum]);
+
+        let result = select("a::G::_const", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Trait(item) = &result[0] {
+            assert_eq!(item.items.len(), 1);
+            assert!(matches!(item.items[0], syn::TraitItem::Const(_)));
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        // Unknown kinds are treated as ordinary idents.
+        assert_eq!(kinds("a::_foo"), vec![]);
+        assert!(Selector::try_from("a::_foo[..]").is_err());
+    }
+
+    #[test]
+    fn typed_wildcard_display() {
+        for path in &["a::_fn", "_mod::_struct[..2]", "_extern_crate"] {
+            assert_eq!(Selector::try_from(path).unwrap().to_string(), *path);
+        }
+    }
+
+    #[test]
+    fn typed_wildcard_nameless() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            use a::*;
+            pub use b::C;
+            extern "C" { fn x(); }
+            foo!();
+            macro_rules! m { () => {} }
+            struct S;"#,
+        )
+        .unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(rendered("_use"), vec!["use a :: * ;", "pub use b :: C ;"]);
+        assert_eq!(
+            rendered("_use").len(),
+            select_all_of_kind(ItemKind::Use, &file).len()
+        );
+        assert_eq!(
+            rendered("_foreign_mod"),
+            vec![r#"extern "C" { fn x () ; }"#]
+        );
+        assert_eq!(
+            rendered("_macro"),
+            vec!["foo ! () ;", "macro_rules ! m { () => { } }"]
+        );
+
+        // Untyped wildcards still only match items with a name.
+        assert_eq!(rendered("_").len(), 2);
+    }
+
+    #[test]
+    fn sibling_names() {
+        let names = |path: &str| {
+            select_with_sibling_names(path, &sample())
+                .unwrap()
+                .into_iter()
+                .map(|(_, siblings)| {
+                    siblings
+                        .iter()
+                        .map(|ident| ident.to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("a::_"), vec![vec!["b"], vec!["b"]]);
+        assert_eq!(names("a::b::C::d"), vec![vec!["f"]]);
+        assert_eq!(names("a::b::C::_::E"), vec![Vec::<String>::new(); 2]);
+
+        let file = sample_enum();
+        let result = select_with_sibling_names("E::C::x", &file).unwrap();
+        assert_eq!(result[0].1, vec![ident("y")]);
+        let result = select_with_sibling_names("E::B", &file).unwrap();
+        assert_eq!(result[0].1, vec![ident("A"), ident("C")]);
+
+        // Results match those of `select`, so an item reached twice by `**` is listed once.
+        let file: syn::File = syn::parse_str("mod a { mod a { struct E; struct F; } }").unwrap();
+        let result = select_with_sibling_names("**::a::**::E", &file).unwrap();
+        assert_eq!(result.len(), select("**::a::**::E", &file).unwrap().len());
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].1, vec![ident("F")]);
+    }
+
+    #[test]
+    fn extern_crate_rename() {
+        let file: syn::File = syn::parse_str(
+            "extern crate foo as use_this;
+            extern crate bar;",
+        )
+        .unwrap();
+
+        assert_eq!(select("use_this", &file).unwrap().len(), 1);
+        assert!(select("foo", &file).unwrap().is_empty());
+        assert_eq!(select("bar", &file).unwrap().len(), 1);
+
+        let original = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .extern_crate_original_name(true)
+                .apply_to(&file)
+                .len()
+        };
+
+        assert_eq!(original("foo"), 1);
+        assert_eq!(original("use_this"), 0);
+        assert_eq!(original("bar"), 1);
+        assert_eq!(original("_extern_crate"), 2);
+    }
+
+    fn node_names(nodes: &[MatchNode]) -> Vec<String> {
+        nodes
+            .iter()
+            .map(|node| match &node.item {
+                Item::Mod(item) => item.ident.to_string(),
+                Item::Fn(item) => item.sig.ident.to_string(),
+                Item::Trait(item) => item.ident.to_string(),
+                Item::Struct(item) => item.ident.to_string(),
+                _ => panic!("Unexpected item in tree"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn tree() {
+        let tree = select_tree("a::_::_", &sample()).unwrap();
+        assert_eq!(node_names(&tree), vec!["a"]);
+        assert_eq!(node_names(&tree[0].children), vec!["b"]);
+        assert_eq!(node_names(&tree[0].children[0].children), vec!["C"]);
+        assert!(tree[0].children[0].children[0].children.is_empty());
+
+        let flat = search_sample("a::_::_");
+        let leaves = tree.iter().flat_map(MatchNode::leaves).collect::<Vec<_>>();
+        assert_eq!(leaves.len(), flat.len());
+    }
+
+    #[test]
+    fn tree_any_depth() {
+        let file: syn::File = syn::parse_str("mod a { mod a { struct E; } }").unwrap();
+        let tree = select_tree("**::a::**::E", &file).unwrap();
+        let leaves = tree.iter().flat_map(MatchNode::leaves).collect::<Vec<_>>();
+        assert_eq!(leaves.len(), select("**::a::**::E", &file).unwrap().len());
+        assert_eq!(leaves.len(), 1);
+        assert_eq!(node_names(&tree), vec!["a"]);
+        assert_eq!(select_ancestors("**::a::**::E", &file).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn tree_through_trait() {
+        let tree = select_tree("a::b::C::_::E", &sample()).unwrap();
+        assert_eq!(node_names(&tree), vec!["a"]);
+        let b = &tree[0].children;
+        assert_eq!(node_names(b), vec!["b"]);
+        let c = &b[0].children;
+        assert_eq!(node_names(c), vec!["C"]);
+        assert_eq!(node_names(&c[0].children), vec!["d", "f"]);
+        for method in &c[0].children {
+            assert_eq!(node_names(&method.children), vec!["E"]);
+            assert!(method.children[0].children.is_empty());
+        }
+    }
+
+    #[test]
+    fn impl_trait_qualified() {
+        let file: syn::File = syn::parse_str(
+            "impl MyType {
+                fn helper() {}
+            }
+            impl Iterator for MyType {
+                fn next() {}
+            }
+            impl Stream for MyType {
+                fn next() {}
+            }",
+        )
+        .unwrap();
+
+        let trait_name = |item: &Item| match item {
+            Item::Impl(item) => item
+                .trait_
+                .as_ref()
+                .map(|(_, path, _)| path.to_token_stream().to_string()),
+            _ => panic!("Result was wrong type"),
+        };
+
+        let result = select("MyType::Iterator::next", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(trait_name(&result[0]).as_deref(), Some("Iterator"));
+        if let Item::Impl(item) = &result[0] {
+            assert_eq!(item.items.len(), 1);
+        }
+
+        let result = select("MyType::Stream", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(trait_name(&result[0]).as_deref(), Some("Stream"));
+
+        assert_eq!(select("MyType::next", &file).unwrap().len(), 2);
+        assert!(select("MyType::Iterator::helper", &file)
+            .unwrap()
+            .is_empty());
+
+        let result = select("MyType::helper", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(trait_name(&result[0]), None);
+    }
+
+    /// An impl's one trait is matched by the last segment of its path, and inherent impls
+    /// never match a trait segment.
+    #[test]
+    fn impl_trait_path() {
+        let file: syn::File = syn::parse_str(
+            "trait Sub: Super {}
+            impl MyType {
+                fn Display() {}
+            }
+            impl std::fmt::Display for MyType {
+                fn fmt() {}
+            }
+            impl<T> From<T> for MyType where T: Copy {
+                fn from() {}
+            }
+            impl Sub for MyType {}
+            impl !Send for MyType {}",
+        )
+        .unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            rendered("MyType::Display"),
+            vec![
+                "impl MyType { fn Display () { } }",
+                "impl std :: fmt :: Display for MyType { fn fmt () { } }",
+            ]
+        );
+        assert_eq!(
+            rendered("MyType::Display::fmt"),
+            vec!["impl std :: fmt :: Display for MyType { fn fmt () { } }"]
+        );
+        assert_eq!(
+            rendered("MyType::From::from"),
+            vec!["impl < T > From < T > for MyType where T : Copy { fn from () { } }"]
+        );
+        assert_eq!(rendered("MyType::Sub").len(), 1);
+        assert!(rendered("MyType::Super").is_empty());
+        assert_eq!(rendered("MyType::Send"), vec!["impl ! Send for MyType { }"]);
+        assert!(rendered("MyType::Display::Display").is_empty());
+    }
+
+    #[test]
+    fn stmts() {
+        let stmts = select_stmts("a::b::C::_::E", &sample()).unwrap();
+        assert_eq!(stmts.len(), 2);
+        for stmt in stmts {
+            match stmt {
+                syn::Stmt::Item(Item::Struct(item)) => assert_eq!(item.ident, ident("E")),
+                _ => panic!("Result was wrong type"),
+            }
+        }
+    }
+
+    #[test]
+    fn aliases() {
+        let mut aliases = std::collections::HashMap::new();
+        aliases.insert("x".to_string(), "a::b".to_string());
+        aliases.insert("x::y".to_string(), "a::b::C".to_string());
+        aliases.insert("_::y".to_string(), "a".to_string());
+
+        let selector = Selector::try_from("x::y::d")
+            .unwrap()
+            .with_aliases(aliases.clone())
+            .unwrap();
+        assert_eq!(selector.to_string(), "a::b::C::d");
+        assert_eq!(selector.apply_to(&sample()).len(), 1);
+
+        let selector = Selector::try_from("x::C")
+            .unwrap()
+            .with_aliases(aliases.clone())
+            .unwrap();
+        assert_eq!(selector.to_string(), "a::b::C");
+
+        let selector = Selector::try_from("z::y")
+            .unwrap()
+            .with_aliases(aliases.clone())
+            .unwrap();
+        assert_eq!(selector.to_string(), "z::y");
+
+        aliases.insert("w".to_string(), "not a path".to_string());
+        assert!(Selector::try_from("x")
+            .unwrap()
+            .with_aliases(aliases)
+            .is_err());
+    }
+
+    /// Descending through a trait or impl member into the items declared in its body must
+    /// move exactly one term further along the path at each level.
+    #[test]
+    fn depth_past_trait_member() {
+        assert_eq!(search_sample("a::b::C::f::E").len(), 1);
+        assert_eq!(search_sample("a::b::C::d::E").len(), 1);
+        assert_eq!(search_sample("a::b::C::_::E").len(), 2);
+        assert!(search_sample("a::b::C::E").is_empty());
+        assert!(search_sample("a::b::C::f::f::E").is_empty());
+
+        let file: syn::File = syn::parse_str(
+            "trait T {
+                fn f() {
+                    mod m {
+                        struct S;
+                    }
+                }
+            }
+            impl U {
+                fn g() {
+                    mod m {
+                        struct S;
+                    }
+                }
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(select("T::f::m::S", &file).unwrap().len(), 1);
+        assert_eq!(select("U::g::m::S", &file).unwrap().len(), 1);
+        assert_eq!(select("_::_::m", &file).unwrap().len(), 2);
+        assert!(select("T::f::S", &file).unwrap().is_empty());
+        assert!(select("U::m::S", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn attr_arg() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[serde(rename = "x")]
+            struct A {
+                #[serde(rename = "x", default)]
+                a: u8,
+                #[serde(rename = "y")]
+                b: u8,
+            }
+            #[serde(rename = "y", deny_unknown_fields)]
+            enum B {
+                #[serde(rename = "x")]
+                V,
+                W,
+            }
+            #[must_use = "x"]
+            fn c() {}
+            #[deprecated(since = "1.0", note = "x")]
+            fn d() {}
+            trait T {
+                #[serde(rename = "x")]
+                fn e();
+                fn f();
+            }
+            #[size(bytes = 4)]
+            struct E;
+            "#,
+        )
+        .unwrap();
+
+        let apply = |path: &str, attr: &str, value: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .with_attr_arg(attr, value)
+                .apply_to(&file)
+        };
+
+        let result = apply("_", "serde::rename", "x");
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.ident, ident("A"));
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        let result = apply("A::_", "serde::rename", "x");
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.fields.len(), 1);
+        }
+
+        let result = apply("B::_", "serde::rename", "x");
+        assert_eq!(result.len(), 1);
+        if let Item::Enum(item) = &result[0] {
+            assert_eq!(item.variants.len(), 1);
+            assert_eq!(item.variants[0].ident, ident("V"));
+        }
+
+        assert_eq!(apply("T::_", "serde::rename", "x").len(), 1);
+        assert_eq!(apply("c", "must_use", "x").len(), 1);
+        assert_eq!(apply("_", "deprecated::note", "x").len(), 1);
+        assert_eq!(apply("_", "size::bytes", "4").len(), 1);
+        assert!(apply("_", "serde::rename", "z").is_empty());
+        assert!(apply("_", "rename", "x").is_empty());
+
+        let selector = Selector::try_from("_")
+            .unwrap()
+            .with_attr_arg("deprecated::since", "1.0")
+            .with_attr_arg("deprecated::note", "x");
+        assert_eq!(selector.apply_to(&file).len(), 1);
+    }
+
+    #[test]
+    fn names() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                fn b() {}
+                struct C { b: u8 }
+                mod d {
+                    fn b() {}
+                }
+            }
+            impl Iterator for C {
+                fn next() {}
+            }
+            fn b() {}",
+        )
+        .unwrap();
+
+        // The two `fn b() {}` are identical, so only one is kept.
+        let results = select_names(&["b", "next", "Iterator"], &file).unwrap();
+        let rendered = results
+            .iter()
+            .map(|item| item.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rendered,
+            vec![
+                "fn b () { }",
+                "struct C { b : u8 }",
+                "impl Iterator for C { fn next () { } }",
+            ]
+        );
+
+        assert!(select_names(&[], &file).unwrap().is_empty());
+        match select_names(&["b", "not a name"], &file) {
+            Err(err) => assert!(matches!(err.kind(), ErrorKind::InvalidSegment(_))),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    fn failing_segment() {
+        let failure = |path: &str| match Selector::try_from(path).unwrap().try_apply_to(&sample()) {
+            Err(error) => error,
+            Ok(_) => panic!("Expected `{}` to match nothing", path),
+        };
+
+        match failure("a::x::C::d").kind() {
+            ErrorKind::ContainerNotFound { position, segment } => {
+                assert_eq!(*position, 1);
+                assert_eq!(segment, "x");
+            }
+            _ => panic!("Wrong error kind"),
+        }
+
+        match failure("a::b::C::x").kind() {
+            ErrorKind::LeafNotFound { position, segment } => {
+                assert_eq!(*position, 3);
+                assert_eq!(segment, "x");
+            }
+            _ => panic!("Wrong error kind"),
+        }
+
+        assert!(matches!(
+            failure("x").kind(),
+            ErrorKind::LeafNotFound { position: 0, .. }
+        ));
+        assert!(matches!(
+            failure("x::b").kind(),
+            ErrorKind::ContainerNotFound { position: 0, .. }
+        ));
+
+        let results = Selector::try_from("a::b::C::d")
+            .unwrap()
+            .try_apply_to(&sample())
+            .unwrap_or_default();
+        assert_eq!(results.len(), 1);
+    }
+
+    #[test]
+    fn unicode_idents() {
+        for path in &["café", "日本語::Größe", "_naïve", "a::Ωmega::_fn"] {
+            let selector = Selector::try_from(path).unwrap();
+            assert_eq!(selector.to_string(), *path);
+        }
+
+        let file: syn::File = syn::parse_str(
+            "mod café {
+                struct 日本語;
+                fn _naïve() {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("café::日本語", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        if let Item::Struct(item) = &result[0] {
+            assert_eq!(item.ident, ident("日本語"));
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        assert_eq!(select("café::_naïve", &file).unwrap().len(), 1);
+        assert!(select("cafe::日本語", &file).unwrap().is_empty());
+        assert!(Selector::try_from("a::b😀").is_err());
+    }
+
+    #[test]
+    fn impl_item_stubs() {
+        let file: syn::File = syn::parse_str(
+            "trait T {
+                /// Docs
+                fn required(&self, x: u8) -> u8;
+                fn provided() {
+                    println!();
+                }
+                const LIMIT: usize;
+                const DEFAULT: u8 = 1;
+                type Output;
+                type Input = u8;
+            }
+            fn free() {}",
+        )
+        .unwrap();
+        let stubs = |path: &str| -> Vec<String> {
+            select_as_impl_items(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            stubs("T::_"),
+            vec![
+                "# [doc = \" Docs\"] fn required (& self , x : u8) -> u8 { unimplemented ! () }",
+                "fn provided () { unimplemented ! () }",
+                "const LIMIT : usize = unimplemented ! () ;",
+                "const DEFAULT : u8 = 1 ;",
+                "type Output = () ;",
+                "type Input = u8 ;",
+            ]
+        );
+        assert_eq!(stubs("T::provided").len(), 1);
+        assert!(stubs("free").is_empty());
+    }
+
+    #[test]
+    fn strip_crate_prefix() {
+        let strip = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .strip_crate_prefix("mycrate")
+        };
+
+        assert_eq!(strip("mycrate::a::b::C").to_string(), "a::b::C");
+        assert_eq!(strip("mycrate::a::b::C").apply_to(&sample()).len(), 1);
+        assert_eq!(strip("other::a::b::C").to_string(), "other::a::b::C");
+        assert_eq!(strip("a::mycrate").to_string(), "a::mycrate");
+        assert_eq!(strip("mycrate").to_string(), "mycrate");
+    }
+
+    #[test]
+    fn methods_only() {
+        let result = Selector::try_from("a::b::C::_")
+            .unwrap()
+            .methods_only()
+            .apply_to(&sample());
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Item::Trait(item) => {
+                assert_eq!(item.items.len(), 1);
+                assert!(matches!(&item.items[0], TraitItem::Fn(method) if method.sig.ident == "f"));
+            }
+            _ => panic!("Expected a trait"),
+        }
+    }
+
+    #[test]
+    fn automatically_derived() {
+        let file: syn::File = syn::parse_str(
+            "struct A;
+            #[automatically_derived]
+            impl Clone for A {
+                fn clone(&self) -> Self {
+                    A
+                }
+            }
+            impl A {
+                fn new() -> Self {
+                    A
+                }
+            }",
+        )
+        .unwrap();
+        assert_eq!(select("A", &file).unwrap().len(), 3);
+
+        let generated = Selector::try_from("A")
+            .unwrap()
+            .automatically_derived(true)
+            .apply_to(&file);
+        assert_eq!(generated.len(), 1);
+        assert!(matches!(&generated[0], Item::Impl(item) if item.trait_.is_some()));
+
+        let hand_written = Selector::try_from("A")
+            .unwrap()
+            .automatically_derived(false)
+            .apply_to(&file);
+        assert_eq!(hand_written.len(), 2);
+        assert!(matches!(&hand_written[0], Item::Struct(_)));
+        assert!(matches!(&hand_written[1], Item::Impl(item) if item.trait_.is_none()));
+    }
+
+    #[test]
+    fn apply_to_items() {
+        let selector = Selector::try_from("b::C::_").unwrap();
+        let a = match &select("a", &sample()).unwrap()[0] {
+            Item::Mod(item) => item.content.as_ref().unwrap().1.clone(),
+            _ => panic!("Expected a module"),
+        };
+
+        let from_vec = selector.apply_to_items(&a);
+        let from_slice = selector.apply_to_items(&a[..1]);
+        let from_array = selector.apply_to_items(&[a[0].clone()]);
+        assert_eq!(from_vec.len(), 1);
+        for results in &[from_slice, from_array] {
+            assert_eq!(results.len(), 1);
+            assert!(same_item(&results[0], &from_vec[0]));
+        }
+
+        assert!(selector.apply_to_items(&a[1..]).is_empty());
+    }
+
+    #[test]
+    fn after_comment() {
+        let src = r#"
+            mod a {
+                pub struct Public;
+
+                // MARK: internal
+                struct Helper;
+                fn helper() {
+                    let s = "// MARK: not a comment";
+                }
+
+                // MARK: tests
+                fn check() {}
+            }"#;
+        let names = |path: &str, marker: &str| -> Vec<String> {
+            select_after_comment(path, marker, src)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            names("a::_", "MARK: internal"),
+            vec![
+                "struct Helper ;",
+                r#"fn helper () { let s = "// MARK: not a comment" ; }"#
+            ]
+        );
+        assert_eq!(names("a::_", "MARK: tests"), vec!["fn check () { }"]);
+        assert_eq!(names("a::_fn", "internal").len(), 2);
+        assert!(names("a::_", "MARK: missing").is_empty());
+    }
+
+    #[test]
+    fn item_keys() {
+        use std::collections::HashSet;
+
+        let file = sample();
+        let mut keys = HashSet::new();
+        for path in &["a::b::C::d::E", "a::b::C::_::E", "a::b::C::f::E"] {
+            keys.extend(select(path, &file).unwrap().into_iter().map(ItemKey::from));
+        }
+
+        // The two structs named `E` have the same tokens, since paths aren't part of an item.
+        assert_eq!(keys.len(), 1);
+
+        keys.extend(select("a::b", &file).unwrap().into_iter().map(ItemKey));
+        assert_eq!(keys.len(), 3);
+    }
+
+    #[test]
+    fn same_items() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                fn b() {}
+            }
+            mod c {
+                fn b  ( ) { }
+            }
+            fn b() -> u8 { 1 }",
+        )
+        .unwrap();
+
+        let from_a = select("a::b", &file).unwrap();
+        let from_c = select("c::b", &file).unwrap();
+        let top = select("b", &file).unwrap();
+        assert!(same_item(&from_a[0], &from_c[0]));
+        assert!(!same_item(&from_a[0], &top[0]));
+
+        let with_cfg: syn::Item = syn::parse_str("#[cfg(test)] fn b() {}").unwrap();
+        assert!(!same_item(&from_a[0], &with_cfg));
+    }
+
+    #[test]
+    fn ancestors() {
+        let names = |path: &str| {
+            select_ancestors(path, &sample())
+                .unwrap()
+                .iter()
+                .map(|item| match item {
+                    Item::Mod(item) => item.ident.to_string(),
+                    Item::Trait(item) => item.ident.to_string(),
+                    Item::Fn(item) => item.sig.ident.to_string(),
+                    _ => panic!("Unexpected ancestor"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names("a::b::C::d"), vec!["a", "b", "C"]);
+        assert_eq!(names("a::b::C::_::E"), vec!["a", "b", "C", "d", "f"]);
+        assert!(names("a").is_empty());
+        assert!(names("a::x").is_empty());
+
+        if let Item::Trait(item) = &select_ancestors("a::b::C::d", &sample()).unwrap()[2] {
+            assert_eq!(item.items.len(), 2);
+        } else {
+            panic!("Result was wrong type");
+        }
+    }
+
+    #[test]
+    fn derives() {
+        let file: syn::File = syn::parse_str(
+            "#[derive(Debug, Clone)]
+            struct A;
+            #[derive(serde::Serialize)]
+            #[derive(Debug)]
+            enum B {}
+            mod c {
+                #[derive(Serialize, Deserialize)]
+                pub struct D;
+            }
+            #[derive(SerializeAs)]
+            struct E;",
+        )
+        .unwrap();
+
+        let count = |path: &str, name: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .derives(name)
+                .apply_to(&file)
+                .len()
+        };
+
+        assert_eq!(count("_", "Debug"), 2);
+        assert_eq!(count("_", "Serialize"), 1);
+        assert_eq!(count("_", "serde::Serialize"), 1);
+        assert_eq!(count("c::_", "Serialize"), 1);
+        assert_eq!(count("_::_", "Deserialize"), 1);
+        assert_eq!(count("_", "Hash"), 0);
+
+        let all = Selector::ends_with("_").unwrap().derives("Serialize");
+        assert_eq!(all.apply_to(&file).len(), 2);
+    }
+
+    #[test]
+    fn map() {
+        let keys = |path: &str, file: &syn::File| {
+            select_map(path, file)
+                .unwrap()
+                .into_keys()
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(keys("a::b", &sample()), vec!["a::b#fn", "a::b#mod"]);
+        assert_eq!(keys("a::b::C::_", &sample()), vec!["a::b::C"]);
+        assert_eq!(
+            keys("a::b::C::_::E", &sample()),
+            vec!["a::b::C::d::E", "a::b::C::f::E"]
+        );
+        assert_eq!(keys("imp::H", &sample_with_cfg()), vec!["imp::H"]);
+
+        let nested: syn::File = syn::parse_str("mod a { mod a { struct E; } }").unwrap();
+        assert_eq!(keys("**::a::**::E", &nested), vec!["a::a::E"]);
+
+        let results = select_map("imp::H", &sample_with_cfg()).unwrap();
+        if let Item::Struct(item) = &results["imp::H"] {
+            assert_eq!(item.fields.to_token_stream().to_string(), "(u8)");
+        } else {
+            panic!("Result was wrong type");
+        }
+
+        let ends_with = Selector::ends_with("E").unwrap();
+        let mut search = super::Search::new(&ends_with);
+        search.search_file(&sample());
+        let paths = search
+            .into_found_results()
+            .into_iter()
+            .map(|found| found.path.join("::"))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, vec!["a::b::C::d::E", "a::b::C::f::E"]);
+    }
+
+    #[test]
+    fn matches_path() {
+        let matches =
+            |selector: &str, path: &str| Selector::try_from(selector).unwrap().matches(path);
+
+        assert!(matches("a::b::C", "a::b::C"));
+        assert!(!matches("a::b::C", "a::b::D"));
+        assert!(matches("a::_::C", "a::x::C"));
+        assert!(matches("a::_fn", "a::x"));
+        assert!(matches("a::handle_*", "a::handle_get"));
+        assert!(matches("S::1", "S::1"));
+        assert!(matches("S::_", "S::1"));
+        assert!(!matches("S::1", "S::01"));
+        assert!(matches("r#type", "r#type"));
+
+        assert!(!matches("a::b", "a::b::c"));
+        assert!(!matches("a::b::c", "a::b"));
+        assert!(!matches("a::_", "a"));
+        assert!(!matches("a", ""));
+        assert!(!matches("a::b", "a::::b"));
+
+        assert!(matches("a::**::E", "a::E"));
+        assert!(matches("a::**::E", "a::b::c::E"));
+        assert!(!matches("a::**::E", "b::E"));
+
+        let ends_with = Selector::ends_with("b::C").unwrap();
+        assert!(ends_with.matches("a::b::C"));
+        assert!(!ends_with.matches("a::b::C::d"));
+    }
+
+    #[test]
+    fn many_files() {
+        let first: syn::File =
+            syn::parse_str(r#"#[cfg(feature = "a")] mod m { struct A; } struct Only;"#).unwrap();
+        let second: syn::File =
+            syn::parse_str(r#"#[cfg(feature = "b")] mod m { struct B; struct A(u8); }"#).unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select_many(path, &[first.clone(), second.clone()])
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            rendered("m::_"),
+            vec![
+                r#"# [cfg (feature = "a")] struct A ;"#,
+                r#"# [cfg (feature = "b")] struct B ;"#,
+                r#"# [cfg (feature = "b")] struct A (u8) ;"#,
+            ]
+        );
+        assert_eq!(rendered("Only").len(), 1);
+        assert!(select_many("m::A", Vec::<&syn::File>::new())
+            .unwrap()
+            .is_empty());
+        assert!(select_many("m::", &[first]).is_err());
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(
+            select_paths("a::_", &sample()).unwrap(),
+            vec!["a::b#mod", "a::b#fn"]
+        );
+        assert_eq!(
+            select_paths("a::b::C::_::E", &sample()).unwrap(),
+            vec!["a::b::C::d::E", "a::b::C::f::E"]
+        );
+        assert_eq!(
+            select_paths("_::H", &sample_with_cfg()).unwrap(),
+            vec!["imp::H", "imp::H"]
+        );
+        assert!(select_paths("a::c", &sample()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn group_by_kind() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                impl B {}
+                fn c() {}
+                struct B;
+                const D: u8 = 0;
+                mod e {}
+                fn f() {}
+                enum G {}
+            }",
+        )
+        .unwrap();
+
+        let kinds = |selector: Selector| {
+            selector
+                .apply_to(&file)
+                .iter()
+                .map(|item| ItemKind::of(item).to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            kinds(Selector::try_from("a::_").unwrap()),
+            vec!["impl", "fn", "struct", "const", "mod", "fn", "enum"]
+        );
+        assert_eq!(
+            kinds(Selector::try_from("a::_").unwrap().group_by_kind(true)),
+            vec!["mod", "struct", "enum", "fn", "fn", "const", "impl"]
+        );
+
+        let grouped = Selector::try_from("a::_fn")
+            .unwrap()
+            .group_by_kind(true)
+            .apply_to(&file);
+        let names = grouped
+            .iter()
+            .map(|item| match item {
+                Item::Fn(item) => item.sig.ident.to_string(),
+                _ => panic!("Result was wrong type"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(names, vec!["c", "f"]);
+
+        // Every way of running a search agrees on the order.
+        let selector = Selector::try_from("a::_").unwrap().group_by_kind(true);
+        let mut search = super::Search::new(&selector);
+        search.search_file(&file);
+        let searched = search
+            .into_results()
+            .iter()
+            .map(|item| ItemKind::of(item).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(searched, kinds(selector.clone()));
+        let top_level = selector
+            .apply_to_with_top_level(&file)
+            .iter()
+            .map(|(item, _)| ItemKind::of(item).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(top_level, kinds(selector));
+    }
+
+    #[test]
+    fn inherent_and_trait_method_same_name() {
+        let file: syn::File = syn::parse_str(
+            "impl MyType {
+                fn foo(&self) {}
+                fn bar(&self) {}
+            }
+            impl Foo for MyType {
+                fn foo(&self) {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("MyType::foo", &file).unwrap();
+        assert_eq!(result.len(), 2);
+
+        let impls = result
+            .iter()
+            .map(|item| match item {
+                Item::Impl(item) => item,
+                _ => panic!("Result was wrong type"),
+            })
+            .collect::<Vec<_>>();
+        assert!(impls[0].trait_.is_none());
+        assert!(impls[1].trait_.is_some());
+        for item in impls {
+            assert_eq!(item.items.len(), 1);
+        }
+    }
+
+    /// A filtered trait impl keeps its whole header, and an unqualified member name matches
+    /// in inherent and trait impls alike.
+    #[test]
+    fn trait_impl_header() {
+        let file: syn::File = syn::parse_str(
+            "impl<T> Widget<T> {
+                fn fmt(&self) {}
+                fn new() -> Self { todo!() }
+            }
+            impl<T: Debug> fmt::Display for Widget<T> where T: Clone {
+                fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result { Ok(()) }
+                fn other() {}
+            }",
+        )
+        .unwrap();
+
+        let result = select("Widget::Display::fmt", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Item::Impl(item) => {
+                assert_eq!(
+                    item.trait_
+                        .as_ref()
+                        .unwrap()
+                        .1
+                        .to_token_stream()
+                        .to_string(),
+                    "fmt :: Display"
+                );
+                assert_eq!(item.generics.to_token_stream().to_string(), "< T : Debug >");
+                assert_eq!(
+                    item.generics.where_clause.to_token_stream().to_string(),
+                    "where T : Clone"
+                );
+                assert_eq!(item.items.len(), 1);
+            }
+            _ => panic!("Result was wrong type"),
+        }
+
+        let result = select("Widget::fmt", &file).unwrap();
+        assert_eq!(result.len(), 2);
+        assert!(result.iter().all(|item| match item {
+            Item::Impl(item) => item.items.len() == 1,
+            _ => false,
+        }));
+    }
+
+    /// Every inherent impl of a type contributes to a path through the type, including
+    /// through a wildcard.
+    #[test]
+    fn multiple_inherent_impls() {
+        let file: syn::File = syn::parse_str(
+            "impl Foo {
+                fn bar(&self) {}
+                fn baz(&self) {}
+            }
+            impl Foo {
+                fn bar_mut(&mut self) {}
+            }
+            impl Other {
+                fn bar(&self) {}
+            }",
+        )
+        .unwrap();
+        let rendered = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            rendered("Foo::bar"),
+            vec!["impl Foo { fn bar (& self) { } }"]
+        );
+        assert_eq!(
+            rendered("Foo::_"),
+            vec![
+                "impl Foo { fn bar (& self) { } fn baz (& self) { } }",
+                "impl Foo { fn bar_mut (& mut self) { } }",
+            ]
+        );
+        assert_eq!(
+            rendered("Foo::_[1..]"),
+            vec!["impl Foo { fn baz (& self) { } }"]
+        );
+        assert_eq!(rendered("_::bar").len(), 2);
+    }
+
+    /// Single names skip the recursive search, which must not change the results.
+    #[test]
+    fn single_name_fast_path() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(a)]
+            mod a {
+                fn a() {}
+            }
+            fn a() {}
+            impl a {}
+            extern crate b as a;
+            #[serde(rename = "x")]
+            struct S;
+            struct S2;
+            "#,
+        )
+        .unwrap();
+
+        let selectors = vec![
+            Selector::try_from("a").unwrap(),
+            Selector::try_from("a")
+                .unwrap()
+                .extern_crate_original_name(true),
+            Selector::try_from("b")
+                .unwrap()
+                .extern_crate_original_name(true),
+            Selector::try_from("S")
+                .unwrap()
+                .with_attr_arg("serde::rename", "x"),
+            Selector::try_from("S2")
+                .unwrap()
+                .with_attr_arg("serde::rename", "x"),
+            Selector::try_from("missing").unwrap(),
+        ];
+
+        for selector in selectors {
+            let mut search = super::Search::new(&selector);
+            search.search_file(&file);
+            let general = search
+                .into_results()
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect::<Vec<_>>();
+            let fast = selector
+                .apply_to(&file)
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect::<Vec<_>>();
+            assert_eq!(fast, general, "{}", selector);
+        }
+
+        assert_eq!(select("a", &file).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn generics() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                fn f<'a, T>(t: &'a T) where T: Clone {}
+                struct S<const N: usize>;
+                impl<T> Tr for S<T> {}
+                static X: u8 = 0;
+            }",
+        )
+        .unwrap();
+
+        let generics = select_generics("a::_", &file)
+            .unwrap()
+            .into_iter()
+            .map(|generics| (generics.params.len(), generics.where_clause.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            generics,
+            vec![(2, true), (1, false), (1, false), (0, false)]
+        );
+    }
+
+    #[test]
+    fn trait_method_default() {
+        let file: syn::File = syn::parse_str(
+            "trait T {
+                const C: u8;
+                fn a();
+                fn b() {}
+                fn c(&self);
+            }
+            impl T for U {
+                fn a() {}
+            }",
+        )
+        .unwrap();
+
+        let names = |with_default: bool| {
+            Selector::try_from("T::_")
+                .unwrap()
+                .with_default(with_default)
+                .apply_to(&file)
+                .iter()
+                .map(|item| match item {
+                    Item::Trait(item) => item
+                        .items
+                        .iter()
+                        .map(|item| match item {
+                            syn::TraitItem::Fn(item) => item.sig.ident.to_string(),
+                            syn::TraitItem::Const(item) => item.ident.to_string(),
+                            _ => panic!("Unexpected trait item"),
+                        })
+                        .collect::<Vec<_>>(),
+                    _ => panic!("Result was wrong type"),
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(names(false), vec![vec!["C", "a", "c"]]);
+        assert_eq!(names(true), vec![vec!["C", "b"]]);
+
+        let impls = Selector::try_from("U::a")
+            .unwrap()
+            .with_default(false)
+            .apply_to(&file);
+        assert_eq!(impls.len(), 1);
+    }
+
+    #[test]
+    fn borrowed_results() {
+        use std::borrow::Cow;
+
+        let file = sample();
+        let is_borrowed = |path: &str| {
+            select_cow(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| matches!(item, Cow::Borrowed(_)))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(is_borrowed("a"), vec![true]);
+        assert_eq!(is_borrowed("a::b"), vec![true, true]);
+        assert_eq!(is_borrowed("a::b::C"), vec![true]);
+        assert_eq!(is_borrowed("a::b::C::d"), vec![false]);
+        // Items inside trait methods can't borrow from the file, since the methods are
+        // converted to free-standing functions to search them.
+        assert_eq!(is_borrowed("a::b::C::_::E"), vec![false, false]);
+
+        let with_cfg = sample_with_cfg();
+        let results = select_cow("imp::H", &with_cfg).unwrap();
+        assert!(results.iter().all(|item| matches!(item, Cow::Owned(_))));
+
+        let owned = to_owned(select_cow("a::_", &file).unwrap())
+            .iter()
+            .map(|item| item.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        let expected = search_sample("a::_")
+            .iter()
+            .map(|item| item.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(owned, expected);
+    }
+
+    #[test]
+    fn wildcard_indices() {
+        let selector = Selector::try_from("a::b::C").unwrap();
+        assert!(!selector.has_wildcard());
+        assert!(selector.wildcard_indices().is_empty());
+
+        let selector = Selector::try_from("_::b::_struct::0::_[1..]").unwrap();
+        assert!(selector.has_wildcard());
+        assert_eq!(selector.wildcard_indices(), vec![0, 2, 4]);
+
+        // An ident that starts with an underscore isn't a wildcard.
+        let selector = Selector::try_from("_private::_unknown_kind").unwrap();
+        assert!(!selector.has_wildcard());
+    }
+
+    /// Methods converted to free-standing functions to search inside them must not keep
+    /// their `self` parameter, which is only valid in a trait or impl.
+    #[test]
+    fn converted_method_receiver() {
+        let result = search_sample("a::b::C::f::E");
+        assert_eq!(result.len(), 1);
+        assert!(matches!(&result[0], Item::Struct(item) if item.ident == "E"));
+
+        let ancestors = select_ancestors("a::b::C::f::E", &sample()).unwrap();
+        match &ancestors[3] {
+            Item::Fn(item) => {
+                assert_eq!(item.sig.ident, ident("f"));
+                assert!(item.sig.inputs.is_empty());
+                assert!(syn::parse_str::<syn::Item>(&item.to_token_stream().to_string()).is_ok());
+            }
+            _ => panic!("Result was wrong type"),
+        }
+
+        let file: syn::File = syn::parse_str(
+            "impl S {
+                fn g(&mut self, x: u8) {
+                    struct Inner;
+                }
+            }",
+        )
+        .unwrap();
+        let tree = select_tree("S::g::Inner", &file).unwrap();
+        match &tree[0].children[0].item {
+            Item::Fn(item) => assert_eq!(item.sig.inputs.to_token_stream().to_string(), "x : u8"),
+            _ => panic!("Result was wrong type"),
+        }
+
+        // The trait itself keeps its receivers when it's the result.
+        if let Item::Trait(item) = &search_sample("a::b::C::f")[0] {
+            if let syn::TraitItem::Fn(item) = &item.items[0] {
+                assert_eq!(item.sig.inputs.len(), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn diagnostics() {
+        let explain = |path: &str, file: &syn::File| {
+            let (results, diagnostics) = Selector::try_from(path)
+                .unwrap()
+                .apply_to_with_diagnostics(file);
+            let messages = diagnostics
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>();
+            (results.len(), messages)
+        };
+
+        let (count, messages) = explain("a::b::C::x", &sample());
+        assert_eq!(count, 0);
+        assert_eq!(
+            messages,
+            vec![
+                "(root) depth 0: `a` (mod) matched `a`, so searching inside it for `b`",
+                "a depth 1: `b` (mod) matched `b`, so searching inside it for `C`",
+                "a::b depth 2: `C` (trait) matched `C`, so searching inside it for `x`",
+                "a::b depth 2: nothing inside `C` (trait) matched `x`",
+                "a depth 1: nothing inside `b` (mod) matched `C`",
+                "a depth 1: `b` (fn) matched `b`, so searching inside it for `C`",
+                "a depth 1: nothing inside `b` (fn) matched `C`",
+                "(root) depth 0: nothing inside `a` (mod) matched `b`",
+                "(root) depth 0: no item in the file matched `a`",
+            ]
+        );
+
+        let (count, messages) = explain("a::b::C::d", &sample());
+        assert_eq!(count, 1);
+        assert!(
+            messages.contains(&"a::b::C depth 3: trait member `d` (fn) matched `d`".to_string())
+        );
+
+        let (count, messages) = explain("x", &sample());
+        assert_eq!(count, 0);
+        assert_eq!(
+            messages,
+            vec!["(root) depth 0: no item in the file matched `x`"]
+        );
+
+        // Collecting diagnostics doesn't change the results.
+        let (count, _) = explain("a::_", &sample());
+        assert_eq!(count, search_sample("a::_").len());
+    }
+
+    #[test]
+    fn trait_members_through_alias() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            trait T {
+                const C: usize;
+                type Out;
+                fn m();
+            }
+
+            type Alias = dyn T;
+
+            impl dyn T {
+                fn helper() {}
+            }
+        "#,
+        )
+        .unwrap();
+
+        let result = select("T::m", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            "trait T { fn m () ; }".to_string()
+        );
+        let result = select("T::_", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            "trait T { const C : usize ; type Out ; fn m () ; }".to_string()
+        );
+
+        // Names aren't resolved through aliases or trait objects, so these paths stop
+        // at the alias or the unnamed impl rather than reaching the trait.
+        assert_eq!(select("Alias", &file).unwrap().len(), 1);
+        assert!(select("Alias::m", &file).unwrap().is_empty());
+        assert!(select("T::helper", &file).unwrap().is_empty());
+
+        match Selector::try_from("Alias::m").unwrap().try_apply_to(&file) {
+            Err(error) => assert!(matches!(
+                error.kind(),
+                ErrorKind::LeafNotFound { position: 1, .. }
+            )),
+            Ok(_) => panic!("Expected `Alias::m` to match nothing"),
+        }
+    }
+
+    #[test]
+    fn all_of_kind() {
+        let names = |kind: ItemKind| -> Vec<String> {
+            select_all_of_kind(kind, &sample())
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(names(ItemKind::Struct), vec!["struct E ;", "struct E ;"]);
+        assert_eq!(names(ItemKind::Mod).len(), 2);
+        assert_eq!(names(ItemKind::Trait).len(), 1);
+        assert_eq!(
+            names(ItemKind::Fn),
+            vec![
+                "fn d () { struct E ; }",
+                "fn f () { struct E ; }",
+                "fn b () { }"
+            ]
+        );
+        assert!(names(ItemKind::Enum).is_empty());
+    }
+
+    #[test]
+    fn with_siblings() {
+        let file: syn::File = syn::parse_str(
+            "fn a() {} fn b() {} fn c() {} fn d() {} fn e() {}
+            mod m { struct S; fn b() {} struct T; }",
+        )
+        .unwrap();
+        let names = |path: &str, count: usize| -> Vec<String> {
+            Selector::try_from(path)
+                .unwrap()
+                .with_siblings(count)
+                .apply_to(&file)
+                .iter()
+                .map(|item| match item {
+                    Item::Fn(item) => item.sig.ident.to_string(),
+                    Item::Struct(item) => item.ident.to_string(),
+                    Item::Mod(item) => item.ident.to_string(),
+                    _ => panic!("Unexpected item"),
+                })
+                .collect()
+        };
+
+        assert_eq!(names("b", 0), vec!["b"]);
+        assert_eq!(names("b", 1), vec!["a", "b", "c"]);
+        assert_eq!(names("e", 2), vec!["c", "d", "e", "m"]);
+        assert_eq!(names("m::b", 5), vec!["S", "b", "T"]);
+        assert_eq!(names("_fn[1..4]", 1), vec!["a", "b", "c", "d", "e"]);
+        assert!(names("x", 3).is_empty());
+    }
+
+    #[test]
+    fn from_items() {
+        let file = sample();
+        let items = match &file.items[0] {
+            Item::Mod(item) => &item.content.as_ref().unwrap().1,
+            _ => panic!("Expected a module"),
+        };
+
+        assert_eq!(select_from_items("b::C::d", items).unwrap().len(), 1);
+        assert_eq!(select_from_items("b", items).unwrap().len(), 2);
+        assert!(select_from_items("a::b", items).unwrap().is_empty());
+        assert!(select_from_items("b", &[]).unwrap().is_empty());
+        assert!(select_from_items("", items).is_err());
+    }
+
+    #[test]
+    fn cfgs() {
+        let results = select_with_cfgs("imp::H", &sample_with_cfg()).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            results[0].cfgs,
+            vec![
+                CfgPredicate::KeyValue {
+                    key: "feature".into(),
+                    value: "g".into()
+                },
+                CfgPredicate::KeyValue {
+                    key: "feature".into(),
+                    value: "h".into()
+                },
+            ]
+        );
+        assert_eq!(
+            results[1].cfgs,
+            vec![CfgPredicate::Not(Box::new(CfgPredicate::KeyValue {
+                key: "feature".into(),
+                value: "g".into()
+            }))]
+        );
+
+        let file: syn::File = syn::parse_str(
+            r#"
+            #[cfg(all(unix, any(test, feature = "x")))]
+            mod m {
+                #![cfg(debug_assertions)]
+                #[cfg(target_os::weird)]
+                fn f() {}
+                fn g() {}
+            }
+            fn h() {}
+        "#,
+        )
+        .unwrap();
+        let cfgs = |path: &str| -> Vec<String> {
+            select_with_cfgs(path, &file).unwrap()[0]
+                .cfgs
+                .iter()
+                .map(ToString::to_string)
+                .collect()
+        };
+
+        assert_eq!(
+            cfgs("m::f"),
+            vec![
+                r#"all(unix, any(test, feature = "x"))"#,
+                "debug_assertions",
+                "target_os :: weird",
+            ]
+        );
+        assert_eq!(cfgs("m::g").len(), 2);
+        assert!(cfgs("h").is_empty());
+    }
+
+    #[test]
+    fn nested_tuple_fields() {
+        let file: syn::File = syn::parse_str(
+            "struct Outer(Inner, u8);
+            struct Inner(u8, Deep<u16>);
+            struct Deep<T>(T);
+            enum E { V(Inner), W { inner: Inner } }
+            struct Looped(Box<Looped>, Looped);",
+        )
+        .unwrap();
+        let fields = |path: &str| -> Vec<String> {
+            select(path, &file)
+                .unwrap()
+                .iter()
+                .map(|item| match item {
+                    Item::Struct(item) => {
+                        format!("{}::{}", item.ident, item.fields.to_token_stream())
+                    }
+                    _ => panic!("Expected a struct"),
+                })
+                .collect()
+        };
+
+        assert_eq!(fields("Outer::0::1"), vec!["Inner::(Deep < u16 >)"]);
+        assert_eq!(fields("Outer::0::1::0"), vec!["Deep::(T)"]);
+        assert_eq!(fields("E::V::0::0"), vec!["Inner::(u8)"]);
+        assert_eq!(fields("E::W::inner::1"), vec!["Inner::(Deep < u16 >)"]);
+        assert_eq!(fields("Outer::_::0"), vec!["Inner::(u8)"]);
+        assert_eq!(fields("Looped::1::1::1"), vec!["Looped::(Looped)"]);
+
+        // Out-of-range indices and fields of types that aren't defined in the file match
+        // nothing.
+        assert!(fields("Outer::2::0").is_empty());
+        assert!(fields("Outer::0::2").is_empty());
+        assert!(fields("Outer::1::0").is_empty());
+        assert!(fields("Looped::0::0").is_empty());
+        assert!(fields("Outer::0::1::0::0").is_empty());
+    }
+
+    #[test]
+    fn selector_order() {
+        let sorted = |paths: &[&str]| -> Vec<String> {
+            let mut selectors = paths
+                .iter()
+                .map(|path| Selector::try_from(*path).unwrap())
+                .collect::<Vec<_>>();
+            selectors.sort();
+            selectors.iter().map(ToString::to_string).collect()
+        };
+
+        assert_eq!(
+            sorted(&["b", "a::c", "a", "a::b", "_", "0", "_fn", "_[1..]", "_[..2]", "_enum"]),
+            vec!["a", "a::b", "a::c", "b", "0", "_", "_[..2]", "_[1..]", "_enum", "_fn"]
+        );
+
+        let set = ["a::b", "a::_", "a::b"]
+            .iter()
+            .map(|path| Selector::try_from(*path).unwrap())
+            .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(set.len(), 2);
+
+        // Options are compared after segments, so selectors that find different results
+        // are never equal.
+        let unanchored = Selector::try_from("a").unwrap().anchored(false);
+        assert_ne!(Selector::try_from("a").unwrap(), unanchored);
+        assert_eq!(unanchored, Selector::ends_with("a").unwrap());
+        assert!(
+            Selector::try_from("a").unwrap() < Selector::try_from("a::b").unwrap().anchored(false)
+        );
+        let set = vec![
+            Selector::try_from("a").unwrap(),
+            unanchored.clone(),
+            unanchored,
+            Selector::try_from("a")
+                .unwrap()
+                .with_attr_arg("serde::rename", "x"),
+        ]
+        .into_iter()
+        .collect::<std::collections::BTreeSet<_>>();
+        assert_eq!(set.len(), 3);
+
+        let resolved = Selector::try_from("a").unwrap().with_mod_resolver(|_| None);
+        assert_eq!(resolved, resolved.clone());
+        assert_eq!(resolved, Selector::try_from("a").unwrap());
+        assert_eq!(
+            resolved.cmp(&Selector::try_from("a").unwrap().with_mod_resolver(|_| None)),
+            std::cmp::Ordering::Equal
+        );
+        assert!(Selector::try_from("a").unwrap() < Selector::try_from("a*").unwrap());
+        assert!(Selector::try_from("a*").unwrap() < Selector::try_from("_").unwrap());
+        assert!(Selector::try_from("_").unwrap() < Selector::try_from("*").unwrap());
+        assert!(Selector::try_from("*::a").unwrap() < Selector::try_from("**::a").unwrap());
+    }
+
+    #[test]
+    fn outermost_only() {
+        let file: syn::File = syn::parse_str(
+            "fn f() { struct E; }
+            struct E;
+            mod m { struct E; struct F; fn g() { struct F; } }
+            impl E {}",
+        )
+        .unwrap();
+        let count = |selector: Selector| selector.apply_to(&file).len();
+
+        let selector = Selector::try_from("E").unwrap().anchored(false);
+        assert_eq!(count(selector.clone()), 4);
+        assert_eq!(count(selector.outermost_only(true)), 2);
+
+        let selector = Selector::try_from("F").unwrap().anchored(false);
+        assert_eq!(count(selector.clone()), 2);
+        let result = selector.outermost_only(true).apply_to(&file);
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            "struct F ;".to_string()
+        );
+
+        // Results at the same depth are all kept.
+        let selector = Selector::try_from("a::b::C::_::E").unwrap();
+        assert_eq!(selector.outermost_only(true).apply_to(&sample()).len(), 2);
+    }
+
+    #[test]
+    fn keep_only_attrs() {
+        let file: syn::File = syn::parse_str(
+            r#"
+            /// Docs
+            #[derive(Debug)]
+            #[allow(dead_code)]
+            #[serde::skip]
+            struct A;
+            mod m {
+                #[allow(unused)]
+                #[serde::skip]
+                fn b() {
+                    #[allow(unused)]
+                    struct C;
+                }
+            }
+        "#,
+        )
+        .unwrap();
+        let render = |path: &str, keep: &[&str]| -> Vec<String> {
+            Selector::try_from(path)
+                .unwrap()
+                .keep_only_attrs(keep)
+                .apply_to(&file)
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(
+            render("A", &["derive", "doc"]),
+            vec![r#"# [doc = " Docs"] # [derive (Debug)] struct A ;"#]
+        );
+        assert_eq!(
+            render("m::b", &["serde::skip"]),
+            vec!["# [serde :: skip] fn b () { # [allow (unused)] struct C ; }"]
+        );
+        assert_eq!(render("m::b::C", &[]), vec!["struct C ;"]);
+        assert_eq!(
+            select("A", &file).unwrap()[0]
+                .to_token_stream()
+                .to_string()
+                .matches("# [")
+                .count(),
+            4
+        );
+    }
+
+    #[test]
+    fn remove() {
+        let removed = |path: &str, src: &str| -> (Vec<String>, String) {
+            let mut file: syn::File = syn::parse_str(src).unwrap();
+            let expected = select(path, &file).unwrap();
+            let results = select_remove(path, &mut file).unwrap();
+            assert_eq!(results.len(), expected.len());
+            (
+                results
+                    .iter()
+                    .map(|item| item.to_token_stream().to_string())
+                    .collect(),
+                file.to_token_stream().to_string(),
+            )
+        };
+
+        let (results, rest) = removed("a::b::C::d", &sample().to_token_stream().to_string());
+        assert_eq!(results, vec!["trait C { fn d () { struct E ; } }"]);
+        assert_eq!(
+            rest,
+            "mod a { mod b { trait C { fn f (self) { struct E ; } } } fn b () { } }"
+        );
+
+        let (_, rest) = removed("a::b::C::_::E", &sample().to_token_stream().to_string());
+        assert_eq!(
+            rest,
+            "mod a { mod b { trait C { fn d () { } fn f (self) { } } } fn b () { } }"
+        );
+
+        // An identical item at another path stays.
+        let (_, rest) = removed("m::f", "mod n { fn f() {} } mod m { fn f() {} }");
+        assert_eq!(rest, "mod n { fn f () { } } mod m { }");
+
+        // An item reached along more than one path by `**` is removed and returned once.
+        let (results, rest) = removed("**::a::**::E", "mod a { mod a { struct E; } }");
+        assert_eq!(results, vec!["struct E ;"]);
+        assert_eq!(rest, "mod a { mod a { } }");
+
+        // Items given the `cfg` attributes of their module are still found.
+        let (results, rest) = removed("imp::H", r#"#[cfg(a)] mod imp { struct H; struct I; }"#);
+        assert_eq!(results, vec!["# [cfg (a)] struct H ;"]);
+        assert_eq!(rest, "# [cfg (a)] mod imp { struct I ; }");
+
+        let (_, rest) = removed("S::b", "struct S { a: u8, b: u16 }");
+        assert_eq!(rest, "struct S { a : u8 }");
+
+        let (_, rest) = removed("E::B", "enum E { A, B(u8), C }");
+        assert_eq!(rest, "enum E { A , C }");
+
+        let (_, rest) = removed("E::B::1", "enum E { A, B(u8, u16) }");
+        assert_eq!(rest, "enum E { A , B (u8) }");
+
+        let (_, rest) = removed("T::m", "struct T; impl T { fn m() {} fn n() {} }");
+        assert_eq!(rest, "struct T ; impl T { fn n () { } }");
+
+        let (results, rest) = removed("x", "fn y() {}");
+        assert!(results.is_empty());
+        assert_eq!(rest, "fn y () { }");
+    }
+
+    #[test]
+    fn trait_member_wildcard() {
+        let render = |path: &str| -> Vec<String> {
+            search_sample(path)
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        // A wildcard as the last segment matches every member of the trait.
+        let all = vec!["trait C { fn d () { struct E ; } fn f (self) { struct E ; } }"];
+        assert_eq!(render("a::b::C::_"), all);
+        assert_eq!(render("a::b::C::_fn"), all);
+        assert_eq!(
+            render("a::b::C::_[1..]"),
+            vec!["trait C { fn f (self) { struct E ; } }"]
+        );
+        assert!(render("a::b::C::_struct").is_empty());
+
+        // A wildcard before the last segment searches inside every member.
+        assert_eq!(render("a::b::C::_::E"), vec!["struct E ;", "struct E ;"]);
+        assert_eq!(render("a::b::C::_::_"), vec!["struct E ;", "struct E ;"]);
+        assert!(render("a::b::C::_::F").is_empty());
+
+        let file: syn::File = syn::parse_str("trait T { const C: u8; type A; fn m(); }").unwrap();
+        assert_eq!(select("T::_", &file).unwrap().len(), 1);
+        assert!(select("T::_::x", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn merge_cfg_variants() {
+        let selector = Selector::try_from("imp::H").unwrap();
+        assert_eq!(selector.apply_to_with_cfgs(&sample_with_cfg()).len(), 2);
+
+        let results = selector
+            .merge_cfg_variants(true)
+            .apply_to_with_cfgs(&sample_with_cfg());
+        assert_eq!(results.len(), 1);
+        assert!(results[0].cfgs.is_empty());
+        assert_eq!(results[0].variants.len(), 2);
+        assert_eq!(
+            results[0].variants[0]
+                .cfgs
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+            vec![r#"feature = "g""#, r#"feature = "h""#]
+        );
+        assert_eq!(
+            results[0].variants[1].cfgs[0].to_string(),
+            r#"not(feature = "g")"#
+        );
+
+        // Shared predicates stay on the merged result, and different kinds aren't merged.
+        let file: syn::File = syn::parse_str(
+            "#[cfg(test)] mod m {
+                #[cfg(unix)] fn f() {}
+                #[cfg(windows)] fn f() {}
+                mod f {}
+                fn g() {}
+            }",
+        )
+        .unwrap();
+        let results = Selector::try_from("m::_")
+            .unwrap()
+            .merge_cfg_variants(true)
+            .apply_to_with_cfgs(&file);
+        let summary = results
+            .iter()
+            .map(|result| (result.cfgs.len(), result.variants.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![(1, 2), (1, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn const_blocks() {
+        let file: syn::File = syn::parse_str(
+            "struct Foo;
+            const _: () = {
+                impl Foo {
+                    fn bar() {}
+                }
+                const _: () = {
+                    struct Hidden;
+                };
+            };
+            mod m {
+                const _: () = {
+                    impl Foo {
+                        fn baz() {}
+                    }
+                };
+            }
+            const NAMED: () = {
+                struct Inner;
+            };",
+        )
+        .unwrap();
+
+        let result = select("Foo::bar", &file).unwrap();
+        assert_eq!(result.len(), 1);
+        assert_eq!(
+            result[0].to_token_stream().to_string(),
+            "impl Foo { fn bar () { } }".to_string()
+        );
+        assert_eq!(select("Hidden", &file).unwrap().len(), 1);
+        assert_eq!(select("m::Foo::baz", &file).unwrap().len(), 1);
+        assert_eq!(select("NAMED::Inner", &file).unwrap().len(), 1);
+        assert!(select("Inner", &file).unwrap().is_empty());
+        assert_eq!(select("Foo", &file).unwrap().len(), 2);
+        assert_eq!(select_all_of_kind(ItemKind::Impl, &file).len(), 2);
+
+        let mut file = file;
+        select_remove("Foo::bar", &mut file).unwrap();
+        assert!(select("Foo::bar", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn parse_prefix() {
+        let (selector, is_prefix) = Selector::parse_prefix("a::b::").unwrap();
+        assert!(is_prefix);
+        assert_eq!(selector.to_string(), "a::b::_");
+        let names = selector
+            .apply_to(&sample())
+            .iter()
+            .map(|item| item.to_token_stream().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            names,
+            vec!["trait C { fn d () { struct E ; } fn f (self) { struct E ; } }"]
+        );
+
+        let (selector, is_prefix) = Selector::parse_prefix("a::b").unwrap();
+        assert!(!is_prefix);
+        assert_eq!(selector.to_string(), "a::b");
+
+        for invalid in &["::", "", "a::::", "a:::", "a::b::c:"] {
+            assert!(
+                Selector::parse_prefix(invalid).is_err(),
+                "`{}` should be rejected",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn items_in_fn_blocks() {
+        let file: syn::File = syn::parse_str(
+            "fn my_fn() {
+                unsafe {
+                    struct Inner;
+                }
+                {
+                    struct Plain;
+                }
+                let _ = async {
+                    struct InLocal;
+                };
+                async {
+                    struct InAsync;
+                };
+            }",
+        )
+        .unwrap();
+
+        assert_eq!(select("my_fn::Inner", &file).unwrap().len(), 1);
+        assert_eq!(select("my_fn::Plain", &file).unwrap().len(), 1);
+        assert_eq!(select("my_fn::InAsync", &file).unwrap().len(), 1);
+        assert!(select("my_fn::InLocal", &file).unwrap().is_empty());
+
+        let mut file = file;
+        assert_eq!(select_remove("my_fn::Inner", &mut file).unwrap().len(), 1);
+        assert!(select("my_fn::Inner", &file).unwrap().is_empty());
+    }
+
+    #[test]
+    fn outline_counts() {
+        let outline = outline(&sample());
+        assert!(outline.ident.is_none());
+        assert_eq!(outline.children.len(), 1);
+
+        let a = &outline.children[0];
+        assert_eq!(a.ident.as_ref().unwrap(), "a");
+        assert_eq!(a.kind, Some(ItemKind::Mod));
+        assert_eq!(a.children.len(), 2);
+        assert_eq!(a.children[1].kind, Some(ItemKind::Fn));
+
+        let c = &a.children[0].children[0];
+        assert_eq!(c.kind, Some(ItemKind::Trait));
+        assert_eq!(
+            c.children
+                .iter()
+                .map(|child| child.ident.as_ref().unwrap().to_string())
+                .collect::<Vec<_>>(),
+            vec!["d", "f"]
+        );
+        assert_eq!(outline.descendant_count(), 8);
+    }
+
+    #[test]
+    fn stop_at_kind() {
+        let selector = |path: &str| {
+            Selector::try_from(path)
+                .unwrap()
+                .stop_at_kind(ItemKind::Trait)
+        };
+        let tokens = |items: Vec<Item>| -> Vec<String> {
+            items
+                .iter()
+                .map(|item| item.to_token_stream().to_string())
+                .collect()
+        };
+
+        let result = selector("a::b::C::d").apply_to(&sample());
+        assert_eq!(result.len(), 1);
+        match &result[0] {
+            Item::Trait(item) => {
+                assert_eq!(item.ident, "C");
+                assert_eq!(item.items.len(), 2);
+            }
+            _ => panic!("Expected a trait"),
+        }
+
+        // The rest of the path still has to match inside the trait.
+        assert!(selector("a::b::C::x").apply_to(&sample()).is_empty());
+
+        // A trait that is itself the last segment is returned as usual.
+        assert_eq!(
+            tokens(selector("a::b::C").apply_to(&sample())),
+            tokens(select("a::b::C", &sample()).unwrap())
+        );
+    }
+
+    #[test]
+    fn macro_export() {
+        let file: syn::File = syn::parse_str(
+            "mod a {
+                #[macro_export]
+                macro_rules! exported {
+                    () => {};
+                }
+                macro_rules! local {
+                    () => {};
+                }
+                fn f() {}
+            }",
+        )
+        .unwrap();
+        let names = |macro_export: Option<bool>| -> Vec<String> {
+            let selector = Selector::try_from("a::_").unwrap();
+            let selector = match macro_export {
+                Some(macro_export) => selector.macro_export(macro_export),
+                None => selector,
+            };
+            selector
+                .apply_to(&file)
+                .iter()
+                .map(|item| match item {
+                    Item::Macro(item) => item.ident.as_ref().unwrap().to_string(),
+                    Item::Fn(item) => item.sig.ident.to_string(),
+                    _ => panic!("Expected a macro or fn"),
+                })
+                .collect()
+        };
+
+        assert_eq!(names(None), vec!["exported", "local", "f"]);
+        assert_eq!(names(Some(true)), vec!["exported", "f"]);
+        assert_eq!(names(Some(false)), vec!["local", "f"]);
+    }
+
+    #[test]
+    fn signatures() {
+        let file: syn::File = syn::parse_str(
+            "trait C {
+                const X: u8;
+                fn d(&self) -> u8;
+                fn e() {}
+            }
+            struct S;
+            impl S {
+                fn f(self, x: u8) {}
+            }
+            fn g<T>() {}",
+        )
+        .unwrap();
+        let signatures = |path: &str| -> Vec<String> {
+            select_signatures(path, &file)
+                .unwrap()
+                .iter()
+                .map(|sig| sig.to_token_stream().to_string())
+                .collect()
+        };
+
+        assert_eq!(signatures("C::d"), vec!["fn d (& self) -> u8"]);
+        assert_eq!(signatures("C::e"), vec!["fn e ()"]);
+        assert_eq!(signatures("C::_"), vec!["fn d (& self) -> u8", "fn e ()"]);
+        assert!(signatures("C::X").is_empty());
+        assert_eq!(signatures("S::f"), vec!["fn f (self , x : u8)"]);
+        assert_eq!(signatures("g"), vec!["fn g < T > ()"]);
+        assert!(signatures("S").len() == 1);
+    }
+
+    #[test]
+    fn extract_source() {
+        let src = r#"
+            #[cfg(feature = "g")]
+            mod imp {
+                /// Docs
+                struct H;
+                fn f() {}
+            }
+        "#;
+        let expected = render(&select("imp::H", &syn::parse_file(src).unwrap()).unwrap());
+        assert_eq!(extract("imp::H", src).unwrap(), expected);
+        assert!(expected.contains("feature"), "{}", expected);
+
+        match extract("imp::H", "mod imp {") {
+            Err(error) => assert!(matches!(error.kind(), ErrorKind::Parse(_))),
+            Ok(_) => panic!("Expected a parse error"),
+        }
+        match extract("imp::G", src) {
+            Err(error) => assert!(matches!(
+                error.kind(),
+                ErrorKind::LeafNotFound { position: 1, .. }
+            )),
+            Ok(_) => panic!("Expected an error"),
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "prettyplease")]
+    fn render_formatted() {
+        let results = select("imp::H", &sample_with_cfg()).unwrap();
+        assert_eq!(
+            render(&results),
+            r#"/// Documentation
+#[cfg(feature = "g")]
+#[serde(skip)]
+#[cfg(feature = "h")]
+pub struct H(u8);
+#[cfg(not(feature = "g"))]
+pub struct H(u16);
+"#
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "prettyplease"))]
+    fn render_unformatted() {
+        let results = select("imp::H", &sample_with_cfg()).unwrap();
+        assert_eq!(
+            render(&results),
+            r#"# [doc = " Documentation"] # [cfg (feature = "g")] # [serde (skip)] # [cfg (feature = "h")] pub struct H (u8) ;
+# [cfg (not (feature = "g"))] pub struct H (u16) ;
+"#
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn at_line() {
+        let file = syn::parse_file(
+            r#"/// Docs
+mod a {
+    struct B;
+
+    trait C {
+        fn d() {
+            struct E;
+        }
+    }
+}
+
+fn f() {}
+"#,
+        )
+        .unwrap();
+        let name = |line: usize| {
+            select_at_line(line, &file).map(|item| match item {
+                Item::Mod(item) => item.ident.to_string(),
+                Item::Struct(item) => item.ident.to_string(),
+                Item::Trait(item) => item.ident.to_string(),
+                Item::Fn(item) => item.sig.ident.to_string(),
+                _ => panic!("Unexpected item"),
+            })
+        };
+
+        assert_eq!(name(1).as_deref(), Some("a"));
+        assert_eq!(name(3).as_deref(), Some("B"));
+        assert_eq!(name(4).as_deref(), Some("a"));
+        assert_eq!(name(5).as_deref(), Some("C"));
+        assert_eq!(name(6).as_deref(), Some("d"));
+        assert_eq!(name(7).as_deref(), Some("E"));
+        assert_eq!(name(10).as_deref(), Some("a"));
+        assert_eq!(name(11), None);
+        assert_eq!(name(12).as_deref(), Some("f"));
+        assert_eq!(name(100), None);
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn spanned() {
+        let file = syn::parse_file(
+            r#"#[cfg(unix)]
+mod a {
+    struct B;
+
+    /// Docs
+    trait C {
+        fn d();
+
+        fn e() {
+            struct F;
+        }
+    }
+}
+"#,
+        )
+        .unwrap();
+        let lines = |path: &str| -> Vec<(usize, usize)> {
+            select_spanned(path, &file)
+                .unwrap()
+                .into_iter()
+                .map(|(_, span)| (span.start().line, span.end().line))
+                .collect()
+        };
+
+        assert_eq!(lines("a::B"), vec![(3, 3)]);
+        assert_eq!(lines("a::C"), vec![(6, 12)]);
+        assert_eq!(lines("a::C::d"), vec![(6, 12)]);
+        assert_eq!(lines("a::C::e::F"), vec![(10, 10)]);
+        assert_eq!(lines("a"), vec![(2, 13)]);
+    }
+
+    #[test]
+    #[cfg(feature = "span-locations")]
+    fn in_range() {
+        let file = syn::parse_file(
+            r#"#[cfg(unix)]
+mod a {
+    struct B;
+
+    /// Docs
+    struct C;
+    struct D {
+        x: u8,
+    }
+}
+"#,
+        )
+        .unwrap();
+        let names = |path: &str, lines: std::ops::Range<usize>| -> Vec<String> {
+            select_in_range(path, &file, lines)
+                .unwrap()
+                .iter()
+                .map(|item| match item {
+                    Item::Mod(item) => item.ident.to_string(),
+                    Item::Struct(item) => item.ident.to_string(),
+                    _ => panic!("Unexpected item"),
+                })
+                .collect()
+        };
+
+        assert_eq!(names("a::_", 1..11), vec!["B", "C", "D"]);
+        assert_eq!(names("a::_", 3..7), vec!["B", "C"]);
+        assert_eq!(names("a::_", 6..8), vec!["C"]);
+        assert_eq!(names("a::_", 7..9), Vec::<String>::new());
+        assert_eq!(names("a", 2..11), vec!["a"]);
+        assert!(names("a::_", 11..20).is_empty());
+    }
 }
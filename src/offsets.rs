@@ -0,0 +1,212 @@
+//! Heuristic location of search results in the original source text.
+//!
+//! Without `proc-macro2`'s `span-locations` feature, spans can't tell us where an item
+//! was declared. Instead, we render the item back to tokens and look for those tokens in
+//! the source, ignoring whitespace and comments on both sides.
+
+use std::ops::Range;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::Item;
+
+use crate::search::Attrs;
+
+/// Find the byte range of the first occurrence of `item` in `src`.
+///
+/// The item's own outer attributes are not part of the returned range, since attributes
+/// may have been added to the result by the search (e.g. `cfg` attributes inherited from
+/// an enclosing module) and doc comments don't render the way they were written.
+pub(crate) fn find(item: &Item, src: &str) -> Option<Range<usize>> {
+    let mut item = item.clone();
+    if let Some(attrs) = item.attrs_mut() {
+        attrs.clear();
+    }
+
+    let needle: String = strip_docs(item.into_token_stream())
+        .to_string()
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+
+    if needle.is_empty() {
+        return None;
+    }
+
+    let (haystack, offsets, _) = condense(src);
+    let start = haystack.find(&needle)?;
+    let last = start + needle.len() - 1;
+
+    // `last` may point into the middle of a multi-byte char in `haystack`, so find
+    // the char it belongs to and map its end back to the original source.
+    let last_char_start = (0..=last).rev().find(|i| haystack.is_char_boundary(*i))?;
+    let last_char_len = haystack[last_char_start..].chars().next()?.len_utf8();
+
+    Some(offsets[start]..offsets[last_char_start] + last_char_len)
+}
+
+/// Remove `#[doc = "..."]` attributes anywhere in a token stream, since doc comments
+/// in the source are treated as comments and skipped.
+fn strip_docs(tokens: TokenStream) -> TokenStream {
+    let mut output = Vec::new();
+    let mut iter = tokens.into_iter().peekable();
+
+    while let Some(tree) = iter.next() {
+        match tree {
+            TokenTree::Punct(punct) if punct.as_char() == '#' => {
+                let mut lookahead = iter.clone();
+                let bang = match lookahead.peek() {
+                    Some(TokenTree::Punct(p)) if p.as_char() == '!' => lookahead.next(),
+                    _ => None,
+                };
+
+                if let Some(TokenTree::Group(group)) = lookahead.peek() {
+                    if is_doc_attr(&group.stream()) {
+                        iter = lookahead;
+                        iter.next();
+                        continue;
+                    }
+                }
+
+                output.push(TokenTree::Punct(punct));
+                if let Some(bang) = bang {
+                    output.push(bang);
+                    iter.next();
+                }
+            }
+            TokenTree::Group(group) => {
+                let mut new =
+                    proc_macro2::Group::new(group.delimiter(), strip_docs(group.stream()));
+                new.set_span(group.span());
+                output.push(TokenTree::Group(new));
+            }
+            other => output.push(other),
+        }
+    }
+
+    output.into_iter().collect()
+}
+
+fn is_doc_attr(tokens: &TokenStream) -> bool {
+    let mut iter = tokens.clone().into_iter();
+    matches!(
+        (iter.next(), iter.next()),
+        (Some(TokenTree::Ident(ident)), Some(TokenTree::Punct(eq))) if ident == "doc" && eq.as_char() == '='
+    )
+}
+
+/// Find the byte range of the section of `src` that follows the first line comment
+/// containing `marker`.
+///
+/// If `marker` contains a colon, as in `MARK: internal`, the section ends at the next line
+/// comment that starts with the text up to that colon, such as `// MARK: public`. Otherwise,
+/// or if there's no such comment, it ends at the end of `src`.
+pub(crate) fn comment_section(src: &str, marker: &str) -> Option<Range<usize>> {
+    let (_, _, comments) = condense(src);
+    let text = |range: &Range<usize>| src[range.start + 2..range.end].trim_start();
+
+    let position = comments
+        .iter()
+        .position(|comment| text(comment).contains(marker))?;
+    let start = comments[position].end;
+    let end = marker
+        .find(':')
+        .and_then(|colon| {
+            let prefix = &marker[..=colon];
+            comments[position + 1..]
+                .iter()
+                .find(|comment| text(comment).starts_with(prefix))
+        })
+        .map_or(src.len(), |comment| comment.start);
+
+    Some(start..end)
+}
+
+type Condensed = (String, Vec<usize>, Vec<Range<usize>>);
+
+/// Remove whitespace and comments from `src`, returning the condensed text, the byte offset
+/// in `src` of every byte in the condensed text, and the byte ranges of the line comments.
+fn condense(src: &str) -> Condensed {
+    let mut text = String::with_capacity(src.len());
+    let mut offsets = Vec::with_capacity(src.len());
+    let mut comments = Vec::new();
+    let mut chars = src.char_indices().peekable();
+    let mut in_string = false;
+
+    let mut push = |text: &mut String, offset: usize, c: char| {
+        text.push(c);
+        offsets.resize(offsets.len() + c.len_utf8(), offset);
+    };
+
+    while let Some((idx, c)) = chars.next() {
+        if in_string {
+            if c == '\\' {
+                push(&mut text, idx, c);
+                if let Some((idx, escaped)) = chars.next() {
+                    if !escaped.is_whitespace() {
+                        push(&mut text, idx, escaped);
+                    }
+                }
+                continue;
+            }
+
+            in_string = c != '"';
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '/' {
+            match chars.peek() {
+                Some((_, '/')) => {
+                    let mut end = src.len();
+                    for (idx, c) in chars.by_ref() {
+                        if c == '\n' {
+                            end = idx;
+                            break;
+                        }
+                    }
+                    comments.push(idx..end);
+                    continue;
+                }
+                Some((_, '*')) => {
+                    chars.next();
+                    let mut depth = 1;
+                    while let Some((_, c)) = chars.next() {
+                        match (c, chars.peek()) {
+                            ('*', Some((_, '/'))) => {
+                                chars.next();
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            ('/', Some((_, '*'))) => {
+                                chars.next();
+                                depth += 1;
+                            }
+                            _ => {}
+                        }
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+        } else if c == '\'' {
+            // Copy char literals such as `'"'` verbatim so they don't open a string.
+            let mut lookahead = chars.clone();
+            if let (Some((i1, c1)), Some((i2, c2))) = (lookahead.next(), lookahead.next()) {
+                if c1 != '\\' && c2 == '\'' {
+                    push(&mut text, idx, c);
+                    push(&mut text, i1, c1);
+                    push(&mut text, i2, c2);
+                    chars = lookahead;
+                    continue;
+                }
+            }
+        }
+
+        if !c.is_whitespace() {
+            push(&mut text, idx, c);
+        }
+    }
+
+    (text, offsets, comments)
+}
@@ -0,0 +1,38 @@
+//! Search results that say whether anything was found.
+
+use syn::Item;
+
+/// The results of a search that parsed successfully, distinguishing a search that found
+/// nothing from one that found results.
+#[derive(Clone)]
+pub enum SelectOutcome {
+    /// No item matched the path.
+    NotFound,
+    /// The results, of which there is at least one.
+    Found(Vec<Item>),
+}
+
+impl SelectOutcome {
+    /// Check if the search found anything.
+    pub fn is_found(&self) -> bool {
+        matches!(self, SelectOutcome::Found(_))
+    }
+
+    /// Get the results, which are empty if nothing was found.
+    pub fn into_items(self) -> Vec<Item> {
+        match self {
+            SelectOutcome::NotFound => Vec::new(),
+            SelectOutcome::Found(items) => items,
+        }
+    }
+}
+
+impl From<Vec<Item>> for SelectOutcome {
+    fn from(items: Vec<Item>) -> Self {
+        if items.is_empty() {
+            SelectOutcome::NotFound
+        } else {
+            SelectOutcome::Found(items)
+        }
+    }
+}
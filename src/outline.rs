@@ -0,0 +1,64 @@
+//! An outline of the items in a file, for navigating it as a tree.
+
+use syn::{Ident, Item};
+
+use crate::search::{children_of, top_level_items, Name};
+use crate::ItemKind;
+
+/// An item in the outline of a file, with the items declared inside it.
+///
+/// The root of an outline is the file itself, which has neither an ident nor a kind.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutlineNode {
+    /// The name of the item, or `None` for the root and for unnamed items such as `use`
+    /// declarations and impls of traits for unnamed types.
+    pub ident: Option<Ident>,
+    /// The kind of the item, or `None` for the root.
+    pub kind: Option<ItemKind>,
+    /// The items inside this one, in document order. These are the items a path can reach
+    /// by adding one segment to the path of this item.
+    pub children: Vec<OutlineNode>,
+}
+
+impl OutlineNode {
+    fn of(item: &Item) -> Self {
+        OutlineNode {
+            ident: item.name().cloned(),
+            kind: Some(item.kind()),
+            children: children_of(item)
+                .iter()
+                .map(|child| OutlineNode::of(child))
+                .collect(),
+        }
+    }
+
+    /// Count the items under this node at any depth.
+    pub fn descendant_count(&self) -> usize {
+        self.children
+            .iter()
+            .map(|child| 1 + child.descendant_count())
+            .sum()
+    }
+}
+
+/// Build an outline of every item in a file, such as for showing it as a tree with the
+/// number of items in each module.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} struct C; }").unwrap();
+/// let outline = syn_select::outline(&file);
+/// let a = &outline.children[0];
+/// assert_eq!(a.ident.as_ref().unwrap(), "a");
+/// assert_eq!(a.children.len(), 2);
+/// ```
+pub fn outline(file: &syn::File) -> OutlineNode {
+    OutlineNode {
+        ident: None,
+        kind: None,
+        children: top_level_items(file)
+            .iter()
+            .map(|item| OutlineNode::of(item))
+            .collect(),
+    }
+}
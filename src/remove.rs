@@ -0,0 +1,238 @@
+//! Removal of search results from the file they were found in.
+//!
+//! Search results are copies, so they're located again by walking the file mutably: an item
+//! is removed when it has the same path as a result and renders to the same tokens, ignoring
+//! `cfg` attributes the search may have copied onto the result from enclosing items. Results
+//! that were filtered down to some of their members, such as a trait narrowed to one method,
+//! remove only those members.
+
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::{Block, Expr, Fields, ImplItem, Item, Stmt, TraitItem};
+
+use crate::search::{Attrs, Found, Name};
+
+/// A result that hasn't been removed from the file yet.
+struct Target {
+    path: Vec<String>,
+    item: Item,
+    removed: bool,
+}
+
+/// Remove each of the results in `found` from `file`, returning the results.
+///
+/// Items declared in `let` initializers and in out-of-line modules can't be reached this
+/// way, so they stay in place.
+pub(crate) fn remove(file: &mut syn::File, found: Vec<Found<'_>>) -> Vec<Item> {
+    let mut targets = found
+        .into_iter()
+        .map(|found| Target {
+            path: found.path,
+            item: found.item.into_owned(),
+            removed: false,
+        })
+        .collect::<Vec<_>>();
+
+    file.items
+        .retain_mut(|item| keep_item(item, &[], &mut targets));
+
+    targets.into_iter().map(|target| target.item).collect()
+}
+
+/// Remove the targets inside `item`, which is found in the container at `path`, returning
+/// whether `item` itself should be kept.
+fn keep_item(item: &mut Item, path: &[String], targets: &mut [Target]) -> bool {
+    let mut item_path = path.to_vec();
+    item_path.extend(item.name().map(ToString::to_string));
+
+    let mut targets_here = targets
+        .iter_mut()
+        .filter(|target| !target.removed && target.path == item_path)
+        .peekable();
+
+    if targets_here.peek().is_some() {
+        let rendered = without_cfgs(item);
+        for target in targets_here {
+            if without_cfgs(&target.item) == rendered {
+                target.removed = true;
+                return false;
+            }
+
+            if remove_members(item, &target.item) {
+                target.removed = true;
+            }
+        }
+    }
+
+    remove_inside(item, &item_path, targets);
+    true
+}
+
+/// Remove the targets nested inside the contents of `item`, which is found at `path`.
+fn remove_inside(item: &mut Item, path: &[String], targets: &mut [Target]) {
+    let member_path = |ident: &syn::Ident| {
+        let mut path = path.to_vec();
+        path.push(ident.to_string());
+        path
+    };
+
+    match item {
+        Item::Mod(item_mod) => {
+            if let Some((_, items)) = &mut item_mod.content {
+                items.retain_mut(|item| keep_item(item, path, targets));
+            }
+        }
+        Item::Fn(item_fn) => remove_from_stmts(&mut item_fn.block.stmts, path, targets),
+        Item::Const(item_const) => {
+            if let Expr::Block(block) = &mut *item_const.expr {
+                // The items of an anonymous const belong to the const's container.
+                let path = if item_const.ident == "_" {
+                    &path[..path.len().saturating_sub(1)]
+                } else {
+                    path
+                };
+                remove_from_stmts(&mut block.block.stmts, path, targets);
+            }
+        }
+        Item::Trait(item_trait) => {
+            for member in &mut item_trait.items {
+                if let TraitItem::Fn(method) = member {
+                    if let Some(block) = &mut method.default {
+                        let path = member_path(&method.sig.ident);
+                        remove_from_stmts(&mut block.stmts, &path, targets);
+                    }
+                }
+            }
+        }
+        Item::Impl(item_impl) => {
+            for member in &mut item_impl.items {
+                if let ImplItem::Fn(method) = member {
+                    let path = member_path(&method.sig.ident);
+                    remove_from_stmts(&mut method.block.stmts, &path, targets);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Remove the targets among the items declared in a function body, including those in
+/// `unsafe`, `async` and plain blocks that are statements of the body.
+fn remove_from_stmts(stmts: &mut Vec<Stmt>, path: &[String], targets: &mut [Target]) {
+    stmts.retain_mut(|stmt| match stmt {
+        Stmt::Item(item) => keep_item(item, path, targets),
+        Stmt::Expr(expr, _) => {
+            if let Some(block) = inner_block_mut(expr) {
+                remove_from_stmts(&mut block.stmts, path, targets);
+            }
+            true
+        }
+        _ => true,
+    });
+}
+
+fn inner_block_mut(expr: &mut Expr) -> Option<&mut Block> {
+    match expr {
+        Expr::Block(expr) => Some(&mut expr.block),
+        Expr::Unsafe(expr) => Some(&mut expr.block),
+        Expr::Async(expr) => Some(&mut expr.block),
+        _ => None,
+    }
+}
+
+/// Remove the members of `item` that were kept in `filtered`, a copy of `item` filtered down
+/// to the members the search matched. Nothing is removed unless every member of `filtered` is
+/// found in `item`, since otherwise `filtered` came from a different item with the same path.
+fn remove_members(item: &mut Item, filtered: &Item) -> bool {
+    match (item, filtered) {
+        (Item::Trait(item), Item::Trait(filtered)) => remove_all(&mut item.items, &filtered.items),
+        (Item::Impl(item), Item::Impl(filtered)) => remove_all(&mut item.items, &filtered.items),
+        (Item::Struct(item), Item::Struct(filtered)) => {
+            remove_fields(&mut item.fields, &filtered.fields)
+        }
+        (Item::Enum(item), Item::Enum(filtered)) => {
+            let contains_all = filtered
+                .variants
+                .iter()
+                .all(|variant| item.variants.iter().any(|own| own.ident == variant.ident));
+            if !contains_all {
+                return false;
+            }
+
+            for variant in &filtered.variants {
+                let rendered = tokens(variant);
+                if item.variants.iter().any(|own| tokens(own) == rendered) {
+                    item.variants = item
+                        .variants
+                        .iter()
+                        .filter(|own| tokens(*own) != rendered)
+                        .cloned()
+                        .collect();
+                } else if let Some(own) = item
+                    .variants
+                    .iter_mut()
+                    .find(|own| own.ident == variant.ident)
+                {
+                    remove_fields(&mut own.fields, &variant.fields);
+                }
+            }
+
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Remove `filtered` from `members` if every one of them is in `members`.
+fn remove_all<T: ToTokens>(members: &mut Vec<T>, filtered: &[T]) -> bool {
+    let filtered = filtered.iter().map(tokens).collect::<Vec<_>>();
+    let rendered = members.iter().map(tokens).collect::<Vec<_>>();
+    if !filtered.iter().all(|member| rendered.contains(member)) {
+        return false;
+    }
+
+    let mut rendered = rendered.into_iter();
+    members.retain(|_| !filtered.contains(&rendered.next().unwrap_or_default()));
+    true
+}
+
+/// Remove the fields in `filtered` from `fields`, if every one of them is in `fields`.
+fn remove_fields(fields: &mut Fields, filtered: &Fields) -> bool {
+    let filtered = filtered.iter().map(tokens).collect::<Vec<_>>();
+    if !filtered
+        .iter()
+        .all(|field| fields.iter().any(|own| &tokens(own) == field))
+    {
+        return false;
+    }
+
+    let keep = |fields: &Punctuated<syn::Field, syn::Token![,]>| {
+        fields
+            .iter()
+            .filter(|field| !filtered.contains(&tokens(*field)))
+            .cloned()
+            .collect()
+    };
+
+    match fields {
+        Fields::Named(named) => named.named = keep(&named.named),
+        Fields::Unnamed(unnamed) => unnamed.unnamed = keep(&unnamed.unnamed),
+        Fields::Unit => {}
+    }
+
+    true
+}
+
+/// Render an item without its `cfg` attributes, which the search may have added.
+fn without_cfgs(item: &Item) -> String {
+    let mut item = item.clone();
+    if let Some(attrs) = item.attrs_mut() {
+        attrs.retain(|attr| !attr.path().is_ident("cfg"));
+    }
+
+    tokens(&item)
+}
+
+fn tokens(node: &impl ToTokens) -> String {
+    node.to_token_stream().to_string()
+}
@@ -0,0 +1,42 @@
+//! Printing search results as Rust source.
+
+use syn::Item;
+
+/// Render search results as Rust source, one item after another.
+///
+/// With the `prettyplease` feature, the output is formatted the way `rustfmt` would format
+/// it, with attributes the search added to a result, such as `cfg` attributes inherited from
+/// an enclosing module, on their own lines after its doc comments. Without it, each item is
+/// printed on one line as `quote` renders its tokens.
+///
+/// # Usage
+/// ```rust,edition2018
+/// let file: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+/// let results = syn_select::select("a::b", &file).unwrap();
+/// assert!(syn_select::render(&results).starts_with("fn b"));
+/// ```
+pub fn render(items: &[Item]) -> String {
+    format(items)
+}
+
+#[cfg(feature = "prettyplease")]
+fn format(items: &[Item]) -> String {
+    prettyplease::unparse(&syn::File {
+        shebang: None,
+        attrs: Vec::new(),
+        items: items.to_vec(),
+    })
+}
+
+#[cfg(not(feature = "prettyplease"))]
+fn format(items: &[Item]) -> String {
+    use quote::ToTokens;
+
+    let mut output = String::new();
+    for item in items {
+        output.push_str(&item.to_token_stream().to_string());
+        output.push('\n');
+    }
+
+    output
+}
@@ -1,21 +1,37 @@
-use crate::selector::SelectorSegment;
-use crate::Selector;
+use crate::diagnostic::{Diagnostic, Diagnostics};
+use crate::filter::Filter;
+use crate::selector::{Options, SelectorSegment};
+use crate::{ItemKind, Selector};
+use proc_macro2::Span;
+use quote::ToTokens;
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::token::Comma;
 use syn::visit::Visit;
 use syn::{
-    self, Attribute, Ident, Item, ItemConst, ItemFn, ItemTrait, ItemType, Stmt, TraitItem,
-    Visibility,
+    self, AttrStyle, Attribute, Block, Expr, ExprLit, Field, Fields, FieldsNamed, FieldsUnnamed,
+    FnArg, Ident, ImplItem, Item, ItemConst, ItemEnum, ItemFn, ItemImpl, ItemStruct, ItemTrait,
+    ItemType, Lit, Meta, Signature, Stmt, TraitItem, Type, TypePath, UseTree, Variant, Visibility,
 };
 
-trait Name {
+pub(crate) trait Name {
     /// Get the name of the item, if it has one.
     fn name(&self) -> Option<&Ident>;
 
-    /// Check if the item is named and matches the sought-after ident.
-    fn is_named(&self, ident: &impl PartialEq<Ident>) -> bool {
+    /// Get the kind of the item.
+    fn kind(&self) -> ItemKind;
+
+    /// Check if the item matches the sought-after term. Items without a name, such as `use`
+    /// declarations, are only matched by a wildcard limited to their kind, e.g. `_use`.
+    fn is_named(&self, term: &SelectorSegment) -> bool {
         if let Some(own) = self.name() {
-            ident == own
+            term == own && term.includes_kind(Some(self.kind()))
         } else {
-            false
+            term.is_typed_wildcard() && term.includes_kind(Some(self.kind()))
         }
     }
 }
@@ -26,7 +42,7 @@ trait TryToItem {
     fn to_item(self) -> Option<Item>;
 }
 
-trait Attrs {
+pub(crate) trait Attrs {
     /// Get all the attributes directly on this item.
     fn attrs(&self) -> Option<&[Attribute]>;
 
@@ -34,12 +50,18 @@ trait Attrs {
 
     /// Get a copy of the `cfg` attributes directly on this item so they can
     /// be added to other items.
+    ///
+    /// This includes inner attributes such as a module's `#![cfg(...)]`, which are
+    /// converted to outer attributes since that's how they'll be applied.
     fn cfg_attrs(&self) -> Vec<Attribute> {
         if let Some(attrs) = self.attrs() {
             attrs
-                .into_iter()
+                .iter()
                 .filter(|attr| attr.path().is_ident("cfg"))
-                .cloned()
+                .map(|attr| Attribute {
+                    style: AttrStyle::Outer,
+                    ..attr.clone()
+                })
                 .collect()
         } else {
             Vec::new()
@@ -68,24 +90,403 @@ trait Attrs {
     }
 }
 
-pub(crate) struct Search<'a> {
-    query: &'a Selector,
-    depth: usize,
-    pub(crate) results: Vec<Item>,
+/// A search result, with information about where it was found.
+///
+/// Results that were found verbatim borrow from the searched file; results that had to be
+/// changed, such as by filtering their members or adding inherited attributes, are owned.
+pub(crate) struct Found<'f> {
+    pub item: Cow<'f, Item>,
+    /// The names of the items from the root of the search to `item`, inclusive. For a
+    /// container filtered down to some of its members, this is the container's path.
+    pub path: Vec<String>,
+    /// The names of the other members of the container the match was made in, if the
+    /// search was collecting them.
+    pub siblings: Vec<Ident>,
+    /// The results found inside this item, if the search is building a tree of matches.
+    /// Leaves of the tree are the results a flat search would return.
+    pub children: Vec<Found<'f>>,
+}
+
+impl<'f> Found<'f> {
+    fn new(item: Cow<'f, Item>, path: Vec<String>, siblings: Vec<Ident>) -> Self {
+        Found {
+            item,
+            path,
+            siblings,
+            children: Vec::new(),
+        }
+    }
+
+    /// Check if this result is a direct child of the searched file, rather than something
+    /// found inside another item such as a module, a function body or a trait.
+    ///
+    /// A container filtered down to some of its members counts as the container.
+    pub(crate) fn is_top_level(&self) -> bool {
+        self.path.len() == usize::from(self.item.name().is_some())
+    }
+
+    /// Take ownership of this result and everything nested inside it, so that it no longer
+    /// borrows from the searched items.
+    fn into_owned<'o>(self) -> Found<'o> {
+        Found {
+            item: Cow::Owned(self.item.into_owned()),
+            path: self.path,
+            siblings: self.siblings,
+            children: self.children.into_iter().map(Found::into_owned).collect(),
+        }
+    }
+
+    /// Apply attributes to this result and to everything nested inside it.
+    fn add_attrs(&mut self, attrs: &[Attribute]) {
+        self.item.to_mut().add_attrs(attrs.to_vec());
+        for child in &mut self.children {
+            child.add_attrs(attrs);
+        }
+    }
 }
 
+/// A search for the items matching a [`Selector`], which can be fed items one at a time.
+///
+/// Most callers should use [`crate::select`] or [`Selector::apply_to`] instead; this type is
+/// for embedding a search into a larger traversal, such as a custom `syn` visitor.
+///
+/// # Usage
+/// ```rust,edition2018
+/// use syn_select::{Search, Selector};
+///
+/// let file: syn::File = syn::parse_str("mod a { struct B; } struct B;").unwrap();
+/// let selector = Selector::try_from("B").unwrap();
+/// let mut search = Search::new(&selector);
+/// for item in &file.items {
+///     search.search_item(item);
+/// }
+/// assert_eq!(search.results().count(), 1);
+/// ```
+pub struct Search<'a>(Walk<'a, 'static>);
+
 impl<'a> Search<'a> {
     /// Create a new search context starting at the top of the given selector
     pub fn new(query: &'a Selector) -> Self {
+        Search(Walk::new(query))
+    }
+
+    /// Search the top-level items of a file.
+    pub fn search_file(&mut self, file: &syn::File) {
+        self.0.search_local(|walk| walk.search_file(file));
+    }
+
+    /// Search each of the items in a container, such as a file or module.
+    pub fn search_items<'i>(&mut self, items: impl IntoIterator<Item = &'i Item>) {
+        self.0.search_local(|walk| walk.search_items(items));
+    }
+
+    /// Search an item, and anything inside it, for matches.
+    ///
+    /// Items passed to this search are treated as siblings in one container, with positions
+    /// counted in the order they were passed for wildcards limited to a range.
+    pub fn search_item(&mut self, item: &Item) {
+        self.0.search_local(|walk| walk.search_item(item));
+    }
+
+    /// Get the results found so far.
+    pub fn results(&self) -> impl Iterator<Item = &Item> {
+        self.0.results.iter().map(|found| &*found.item)
+    }
+
+    /// Finish the search and take ownership of its results.
+    pub fn into_results(self) -> Vec<Item> {
+        self.0.into_results()
+    }
+
+    /// Finish the search and take ownership of its results, each with the span of the source
+    /// it was found in. See [`crate::select_spanned`] for what the span covers.
+    pub fn into_spanned_results(self) -> Vec<(Item, Span)> {
+        self.0
+            .into_found_results()
+            .into_iter()
+            .map(|found| {
+                let span = span_without_attrs(&found.item);
+                (found.item.into_owned(), span)
+            })
+            .collect()
+    }
+
+    /// Record the names of the other members of the container each result was found in.
+    /// This only knows the members of a whole file or of containers found by the search.
+    pub(crate) fn collect_sibling_names(self) -> Self {
+        Search(self.0.collect_sibling_names())
+    }
+
+    /// Nest each result under the item it was found in, rather than returning a flat list.
+    pub(crate) fn build_tree(self) -> Self {
+        Search(self.0.build_tree())
+    }
+
+    /// Finish the search and take ownership of its results, with their metadata, after
+    /// applying the selector's options for results.
+    pub(crate) fn into_found_results(self) -> Vec<Found<'static>> {
+        self.0.into_found_results()
+    }
+}
+
+impl<'a> From<&'a Selector> for Search<'a> {
+    fn from(query: &'a Selector) -> Self {
+        Search::new(query)
+    }
+}
+
+/// The traversal behind a [`Search`], which can borrow its results from the items it
+/// searches for as long as those items live, `'f`.
+pub(crate) struct Walk<'q, 'f> {
+    query: &'q Selector,
+    depth: usize,
+    /// The position in its container of the next item passed to `search_item`.
+    position: usize,
+    /// The names of the items the search has descended through to reach this point.
+    path: Vec<String>,
+    /// The `#[path]` attributes of the out-of-line modules loaded on the way to this point,
+    /// used to avoid following circular module declarations.
+    loaded: HashSet<String>,
+    /// The names of the items in the container being searched, which are only kept
+    /// when the search is collecting sibling names.
+    sibling_names: Option<Vec<Option<Ident>>>,
+    /// The items in the container being searched that were borrowed from the searched
+    /// file, used to find the definitions of the types of fields the search passes through.
+    scope: Vec<&'f Item>,
+    /// Whether results are nested under the items they were found in.
+    tree: bool,
+    /// Where to record the search's decisions, if anywhere.
+    diagnostics: Option<Diagnostics>,
+    /// A callback that loads out-of-line modules for this search only, used instead of the
+    /// selector's resolver.
+    loader: Option<&'q LoadFn<'q>>,
+    /// The addresses of the items this search and the searches it started have already
+    /// checked against a `**` term, with the depth of the term. An item can be reached at
+    /// the same `**` through each of several ancestors, but only the first visit finds anything.
+    visited: Rc<RefCell<HashSet<(usize, usize)>>>,
+    results: Vec<Found<'f>>,
+}
+
+/// A callback that loads the contents of an out-of-line module, given the path to the module
+/// and the value of its `#[path]` attribute.
+pub(crate) type LoadFn<'l> = dyn Fn(&[String], Option<&str>) -> Option<syn::File> + 'l;
+
+impl<'q, 'f> Walk<'q, 'f> {
+    /// Create a new search starting at the top of the given selector.
+    pub(crate) fn new(query: &'q Selector) -> Self {
         Self {
             query,
             depth: 0,
+            position: 0,
+            path: Vec::new(),
+            loaded: HashSet::new(),
+            sibling_names: None,
+            scope: Vec::new(),
+            tree: false,
+            diagnostics: None,
+            loader: None,
+            visited: Rc::default(),
             results: vec![],
         }
     }
 
-    pub fn search_file(&mut self, file: &syn::File) {
-        self.visit_file(file)
+    /// Load out-of-line modules with `loader` rather than the selector's resolver.
+    pub(crate) fn with_loader(mut self, loader: &'q LoadFn<'q>) -> Self {
+        self.loader = Some(loader);
+        self
+    }
+
+    /// Search the top-level items of a file.
+    pub(crate) fn search_file(&mut self, file: &'f syn::File) {
+        let contents = top_level_items(file);
+        if self.sibling_names.is_some() {
+            self.sibling_names = Some(names_of(contents.iter().map(|item| &**item)));
+        }
+
+        let found = self.results.len();
+        self.search_contents(contents);
+        if self.results.len() == found {
+            self.note(|| format!("no item in the file matched `{}`", self.term()));
+        }
+    }
+
+    /// Search each of the items in a container, such as a file or module.
+    pub(crate) fn search_items(&mut self, items: impl IntoIterator<Item = &'f Item>) {
+        self.search_contents(with_anonymous_const_items(
+            items.into_iter().map(Cow::Borrowed).collect(),
+        ));
+    }
+
+    /// Search an item, and anything inside it, for matches.
+    fn search_item(&mut self, item: &'f Item) {
+        let index = self.position;
+        self.position += 1;
+        self.search_item_at(index, item);
+    }
+
+    /// Search items that may have been created by the search, such as trait members
+    /// converted to free-standing items, rather than borrowed from the searched file.
+    fn search_contents(&mut self, contents: Vec<Cow<'f, Item>>) {
+        let context = self.context(&contents);
+        self.scope = contents
+            .iter()
+            .filter_map(|item| match item {
+                Cow::Borrowed(item) => Some(*item),
+                Cow::Owned(_) => None,
+            })
+            .collect();
+        for (item, is_context) in contents.into_iter().zip(context) {
+            if is_context {
+                self.results
+                    .push(Found::new(item.clone(), self.path_to(&item), Vec::new()));
+            }
+
+            match item {
+                Cow::Borrowed(item) => self.search_item(item),
+                Cow::Owned(item) => {
+                    let index = self.position;
+                    self.position += 1;
+                    self.search_local(|walk| walk.search_item_at(index, &item));
+                }
+            }
+        }
+    }
+
+    /// Find which of the items in a container should be returned because they're near a
+    /// result in it, when the selector asks for siblings. The results themselves aren't
+    /// included.
+    fn context(&self, contents: &[Cow<'f, Item>]) -> Vec<bool> {
+        let count = self.query.options().with_siblings;
+        let mut context = vec![false; contents.len()];
+        if count == 0 {
+            return context;
+        }
+
+        let matched = contents
+            .iter()
+            .enumerate()
+            .map(|(offset, item)| self.is_result(self.position + offset, item))
+            .collect::<Vec<_>>();
+
+        for (index, _) in matched.iter().enumerate().filter(|(_, m)| **m) {
+            let end = (index + count + 1).min(contents.len());
+            for near in index.saturating_sub(count)..end {
+                context[near] = !matched[near];
+            }
+        }
+
+        context
+    }
+
+    /// Run `search` on a copy of this search that can borrow from items which don't live
+    /// as long as this search's results, then take ownership of what it found.
+    fn search_local<'l>(&mut self, search: impl FnOnce(&mut Walk<'q, 'l>)) {
+        let mut local = Walk {
+            query: self.query,
+            depth: self.depth,
+            position: self.position,
+            path: self.path.clone(),
+            loaded: self.loaded.clone(),
+            sibling_names: self.sibling_names.take(),
+            scope: Vec::new(),
+            tree: self.tree,
+            diagnostics: self.diagnostics.clone(),
+            loader: self.loader,
+            // The items searched here may not outlive this call, so their addresses could be
+            // reused by other items once it returns.
+            visited: Rc::default(),
+            results: Vec::new(),
+        };
+
+        search(&mut local);
+
+        self.position = local.position;
+        self.sibling_names = local.sibling_names;
+        self.results
+            .extend(local.results.into_iter().map(Found::into_owned));
+    }
+
+    /// Finish the search and take ownership of its results.
+    pub(crate) fn into_results(self) -> Vec<Item> {
+        self.into_borrowed_results()
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect()
+    }
+
+    /// Finish the search, returning results that weren't changed by the search as
+    /// borrowed from the searched items.
+    pub(crate) fn into_borrowed_results(self) -> Vec<Cow<'f, Item>> {
+        self.into_found_results()
+            .into_iter()
+            .map(|found| found.item)
+            .collect()
+    }
+
+    /// Finish the search, returning the results with their metadata after applying the
+    /// selector's options for results.
+    ///
+    /// When the search built a tree, the options apply to its leaves, which are the results
+    /// a flat search would return, and containers left without any leaves are dropped.
+    pub(crate) fn into_found_results(mut self) -> Vec<Found<'f>> {
+        let options = self.query.options();
+        if options.outermost_only {
+            keep_outermost(&mut self.results);
+        }
+
+        if self.query.has_any_depth() {
+            dedup_results(&mut self.results);
+        }
+
+        let mut results = map_leaves(self.results, &mut |found| Found {
+            item: retain_attrs(found.item, options),
+            ..found
+        });
+
+        // A tree keeps the nesting of the items it was found in, which grouping would undo.
+        if !self.tree {
+            sort_by_kind(&mut results, options, |found| &found.item);
+        }
+
+        results
+    }
+
+    /// Record the names of the other members of the container each result was found in.
+    fn collect_sibling_names(mut self) -> Self {
+        self.sibling_names = Some(Vec::new());
+        self
+    }
+
+    /// Nest each result under the item it was found in, rather than returning a flat list.
+    fn build_tree(mut self) -> Self {
+        self.tree = true;
+        self
+    }
+
+    /// Record the decisions the search makes, which can be taken with `take_diagnostics`.
+    pub(crate) fn collect_diagnostics(mut self) -> Self {
+        self.diagnostics = Some(Diagnostics::default());
+        self
+    }
+
+    /// Take the decisions recorded so far, if the search is collecting them.
+    pub(crate) fn take_diagnostics(&self) -> Vec<Diagnostic> {
+        self.diagnostics
+            .as_ref()
+            .map(Diagnostics::take)
+            .unwrap_or_default()
+    }
+
+    /// Record a decision if the search is collecting diagnostics. The message is only built
+    /// when it will be kept.
+    fn note(&self, message: impl FnOnce() -> String) {
+        if let Some(diagnostics) = &self.diagnostics {
+            diagnostics.push(Diagnostic {
+                depth: self.depth,
+                path: self.path.clone(),
+                message: message(),
+            });
+        }
     }
 
     /// Get the currently sought term from the provided query path
@@ -97,140 +498,1164 @@ impl<'a> Search<'a> {
         self.depth == self.query.len() - 1
     }
 
-    /// Start a new search for the next term in the path within the specified item.
-    fn search_deeper(&self, item: &syn::Item) -> Self {
-        let mut new = Self {
-            depth: self.depth + 1,
-            ..Search::new(self.query)
+    /// Check if the item at position `index` in its container matches the current term.
+    fn is_match(&self, index: usize, item: &Item) -> bool {
+        let term = self.term();
+        if !term.includes_position(index) {
+            return false;
+        }
+
+        match item {
+            Item::ExternCrate(item) if self.query.options().extern_crate_original_name => {
+                term == &item.ident && term.includes_kind(Some(ItemKind::ExternCrate))
+            }
+            Item::Use(item_use)
+                if self.query.options().match_reexports || self.query.options().match_imports =>
+            {
+                item.is_named(term)
+                    || ((self.query.options().match_imports
+                        || matches!(item_use.vis, Visibility::Public(_)))
+                        && term.includes_kind(Some(ItemKind::Use))
+                        && imported_names(&item_use.tree).any(|name| term == name))
+            }
+            _ => item.is_named(term),
+        }
+    }
+
+    /// Check if the item at position `index` in the top-level container would be a result
+    /// of a single-segment query, without searching inside it.
+    pub(crate) fn is_result(&self, index: usize, item: &Item) -> bool {
+        self.can_match() && self.is_match(index, item) && self.accepts(item)
+    }
+
+    /// Get the conditions a result's attributes must satisfy.
+    fn filters(&self) -> &[Filter] {
+        &self.query.options().filters
+    }
+
+    /// Check if an item with the given attributes satisfies the selector's filters.
+    fn passes_filters(&self, attrs: &[Attribute]) -> bool {
+        self.filters().iter().all(|filter| filter.matches(attrs))
+    }
+
+    /// Check if a matched item satisfies the selector's filters and, for `macro_rules!`
+    /// definitions, its export requirement.
+    fn accepts(&self, item: &Item) -> bool {
+        if !self.passes_filters(item.attrs().unwrap_or_default()) {
+            return false;
+        }
+
+        match (item, self.query.options().macro_export) {
+            (Item::Macro(item_macro), Some(macro_export))
+                if item_macro.mac.path.is_ident("macro_rules") =>
+            {
+                let exported = item_macro
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path().is_ident("macro_export"));
+                exported == macro_export
+            }
+            _ => true,
+        }
+    }
+
+    /// Whether the start of the query should also be sought inside every item this search
+    /// encounters, rather than only in the top-level container.
+    fn restarts(&self) -> bool {
+        self.depth == 0 && !self.query.options().anchored
+    }
+
+    /// Start a new search for the next term in the path within the specified item, which
+    /// matched the current term.
+    fn search_deeper(&self, item: &'f Item) -> Vec<Found<'f>> {
+        self.search_children(item, self.depth + 1)
+    }
+
+    /// Search the children of an item that was created by the search, such as a trait
+    /// member converted to a free-standing item. The results are owned, since they can't
+    /// borrow from `item`.
+    fn search_owned_children(&self, item: &Item, depth: usize) -> Vec<Found<'f>> {
+        let local = Walk {
+            query: self.query,
+            depth: self.depth,
+            position: 0,
+            path: self.path.clone(),
+            loaded: self.loaded.clone(),
+            sibling_names: self.sibling_names.clone(),
+            scope: self.scope.clone(),
+            tree: self.tree,
+            diagnostics: self.diagnostics.clone(),
+            loader: self.loader,
+            visited: Rc::default(),
+            results: Vec::new(),
         };
 
-        if new.depth < new.query.len() {
-            for content in contents_of_item(item) {
-                new.visit_item(&content);
+        local
+            .search_children(item, depth)
+            .into_iter()
+            .map(Found::into_owned)
+            .collect()
+    }
+
+    /// Search inside a trait or impl member that was converted to a free-standing item,
+    /// for the term at `depth` in the query path.
+    fn search_member(&self, member: Item, depth: usize) -> Vec<Found<'f>> {
+        let found = self.search_owned_children(&member, depth);
+        self.nest(Cow::Owned(member), found)
+    }
+
+    /// Search the children of `item` for the term at `depth` in the query path.
+    fn search_children(&self, item: &'f Item, depth: usize) -> Vec<Found<'f>> {
+        self.search_children_from(self.path.clone(), item, depth)
+    }
+
+    /// Search the children of `item`, which is found at `path`, for the term at `depth` in the
+    /// query path.
+    fn search_children_from(
+        &self,
+        path: Vec<String>,
+        item: &'f Item,
+        depth: usize,
+    ) -> Vec<Found<'f>> {
+        if self.query.part(depth).is_any_depth()
+            && matches!(
+                item,
+                Item::Trait(_) | Item::Impl(_) | Item::Enum(_) | Item::Struct(_)
+            )
+        {
+            return self.search_members_at_any_depth(path, item, depth);
+        }
+
+        let contents = match item {
+            Item::Trait(_) | Item::Impl(_) | Item::Enum(_) | Item::Struct(_) => Vec::new(),
+            _ => {
+                let contents = contents_of_item(item, self.query.options());
+                let is_out_of_line =
+                    matches!(item, Item::Mod(item_mod) if item_mod.content.is_none());
+                // Most items, such as functions without nested items, have nothing to search,
+                // so skip setting up a search of them.
+                if contents.is_empty() && !is_out_of_line {
+                    return Vec::new();
+                }
+
+                contents
+            }
+        };
+
+        let mut new = self.child_walk(path, item, depth);
+        match item {
+            Item::Trait(item_trait) => {
+                ItemTraitSearch::new(&new).search(item_trait, &item.cfg_attrs())
             }
+            Item::Impl(item_impl) => {
+                // A segment naming the implemented trait narrows the search to this impl,
+                // e.g. `MyType::Iterator::next`; otherwise the segment names a member.
+                if new.names_trait(item_impl) {
+                    if new.can_match() {
+                        return vec![Found::new(Cow::Borrowed(item), new.path, Vec::new())];
+                    }
+
+                    new.depth += 1;
+                }
+
+                ItemImplSearch::new(&new).search(item_impl, &item.cfg_attrs())
+            }
+            Item::Enum(item_enum) => new.search_enum(item_enum),
+            Item::Struct(item_struct) => new.search_struct(item_struct),
+            _ => {
+                if new.sibling_names.is_some() {
+                    new.sibling_names = Some(names_of(contents.iter().map(|item| &**item)));
+                }
+
+                new.search_contents(contents);
+
+                if let Some(file) = new.resolve_mod(item) {
+                    new.search_local(|walk| walk.search_file(&file));
+                }
+
+                new.with_attrs(item.cfg_attrs())
+            }
+        }
+    }
+
+    /// Create a search of the children of `item`, which is found at `path`, for the term at
+    /// `depth` in the query path.
+    fn child_walk(&self, path: Vec<String>, item: &Item, depth: usize) -> Self {
+        let mut new = Self {
+            depth,
+            path,
+            loaded: self.loaded.clone(),
+            sibling_names: self.sibling_names.as_ref().map(|_| Vec::new()),
+            scope: self.scope.clone(),
+            tree: self.tree,
+            diagnostics: self.diagnostics.clone(),
+            loader: self.loader,
+            visited: self.visited.clone(),
+            ..Walk::new(self.query)
+        };
+
+        if let Some(name) = item.name() {
+            new.path.push(name.to_string());
         }
 
         new
     }
 
-    /// Apply attributes to the results and return them
-    fn with_attrs(mut self, attrs: Vec<Attribute>) -> Vec<Item> {
-        if attrs.is_empty() {
-            return self.results;
+    /// Search a trait, impl, enum or struct, found at `path`, when the term at `depth` is
+    /// `**`. The members are matched against the term after `**`, and the bodies of trait
+    /// and impl members are searched for `**` again, since they can contain items.
+    fn search_members_at_any_depth(
+        &self,
+        path: Vec<String>,
+        item: &'f Item,
+        depth: usize,
+    ) -> Vec<Found<'f>> {
+        let mut found = self.search_children_from(path.clone(), item, depth + 1);
+        if self.query.part(depth + 1).is_any_depth() {
+            return found;
         }
 
-        for item in &mut self.results {
-            item.add_attrs(attrs.clone());
-        }
+        let options = self.query.options();
+        let members = match item {
+            Item::Trait(item_trait) => item_trait
+                .items
+                .iter()
+                .filter(|member| member_has_contents(trait_member_body(member), options))
+                .filter_map(|member| member.clone().to_item())
+                .collect(),
+            Item::Impl(item_impl) => item_impl
+                .items
+                .iter()
+                .filter(|member| member_has_contents(impl_member_body(member), options))
+                .filter_map(|member| member.clone().to_item())
+                .collect(),
+            _ => Vec::new(),
+        };
 
-        self.results
+        let walk = self.child_walk(path, item, depth);
+        let mut in_members = members
+            .into_iter()
+            .flat_map(|member| walk.search_member(member, depth))
+            .collect::<Vec<_>>();
+        add_cfgs(&mut in_members, &item.cfg_attrs());
+        found.extend(in_members);
+
+        found
     }
-}
 
-impl<'a> From<&'a Selector> for Search<'a> {
-    fn from(query: &'a Selector) -> Self {
-        Search::new(query)
+    /// Check if the current term is the name of the trait implemented by `item_impl`.
+    /// Wildcards don't count, so that they keep matching the impl's members.
+    ///
+    /// The trait can only be named right after the impl was matched, so this is never
+    /// true when an unanchored search restarts inside the impl.
+    fn names_trait(&self, item_impl: &ItemImpl) -> bool {
+        if self.depth == 0 {
+            return false;
+        }
+
+        match (self.term(), trait_ident(item_impl)) {
+            (SelectorSegment::Ident(own), Some(ident)) => ident == own,
+            _ => false,
+        }
     }
-}
 
-impl<'a, 'ast> Visit<'ast> for Search<'a> {
-    fn visit_item(&mut self, item: &'ast Item) {
-        let search_term = self.term();
+    /// Load the contents of an out-of-line module declaration such as `mod foo;`, unless
+    /// its `#[path]` attribute names a file that's already being searched further up the
+    /// current path. Modules without the attribute are each loaded from their own file below
+    /// their parent's, so only the attribute can lead back to a file being searched.
+    ///
+    /// This expects `self.path` to already end with the module's name.
+    fn resolve_mod(&mut self, item: &syn::Item) -> Option<syn::File> {
+        let file_path = match item {
+            Item::Mod(item_mod) if item_mod.content.is_none() => path_attr(&item_mod.attrs),
+            _ => return None,
+        };
 
-        if !item.is_named(search_term) {
-            return;
+        if let Some(file_path) = &file_path {
+            if self.loaded.contains(file_path) {
+                self.note(|| format!("skipped `{}`, which is already being searched", file_path));
+                return None;
+            }
         }
 
-        // If we're on the last term of the path, we can go ahead and match
-        // right now.
+        let file = match self.loader {
+            Some(loader) => loader(&self.path, file_path.as_deref()),
+            None => self.query.resolve_mod(&self.path, file_path.as_deref()),
+        };
+        let file = match file {
+            Some(file) => file,
+            None => {
+                self.note(|| "no file was resolved for this out-of-line module".into());
+                return None;
+            }
+        };
+
+        self.note(|| "searching the resolved file for this out-of-line module".into());
+        self.loaded.extend(file_path);
+        Some(file)
+    }
+
+    /// Find variants matching the query inside the given enum, or fields inside those variants.
+    /// This returns a filtered enum if one or more variants matched.
+    ///
+    /// This expects `self.depth` to point at the variant term of the query.
+    fn search_enum(&self, item_enum: &ItemEnum) -> Vec<Found<'f>> {
+        let term = self.term();
+        let is_match = |index: usize| {
+            let variant = &item_enum.variants[index];
+            term == &variant.ident && term.includes_kind(None) && term.includes_position(index)
+        };
+
         if self.can_match() {
-            self.results.push(item.clone());
-            return;
+            let is_match = |index: usize| {
+                is_match(index) && self.passes_filters(&item_enum.variants[index].attrs)
+            };
+            let variants = item_enum
+                .variants
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_match(*index))
+                .map(|(_, variant)| variant.clone())
+                .collect::<Punctuated<_, _>>();
+
+            if variants.is_empty() {
+                return Vec::new();
+            }
+
+            let siblings = self.siblings(
+                item_enum
+                    .variants
+                    .iter()
+                    .map(|variant| Some(&variant.ident)),
+                is_match,
+            );
+
+            let mut result = item_enum.clone();
+            result.variants = variants;
+            return vec![Found::new(
+                Cow::Owned(result.into()),
+                self.path.clone(),
+                siblings,
+            )];
+        }
+
+        // Past the fields of a variant, the search continues in the definitions of their types.
+        if self.depth + 1 != self.query.len() - 1 {
+            return item_enum
+                .variants
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| is_match(*index))
+                .flat_map(|(_, variant)| self.search_field_types(&variant.fields, self.depth + 1))
+                .collect();
+        }
+
+        let field_term = self.query.part(self.depth + 1);
+        let mut siblings = Vec::new();
+        let variants = item_enum
+            .variants
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| is_match(*index))
+            .filter_map(|(_, variant)| {
+                let fields = filter_fields(&variant.fields, field_term, self.filters())?;
+                siblings.extend(self.field_siblings(&variant.fields, field_term));
+                Some(Variant {
+                    fields,
+                    ..variant.clone()
+                })
+            })
+            .collect::<Punctuated<_, _>>();
+
+        if variants.is_empty() {
+            return Vec::new();
+        }
+
+        let mut result = item_enum.clone();
+        result.variants = variants;
+        vec![Found::new(
+            Cow::Owned(result.into()),
+            self.path.clone(),
+            siblings,
+        )]
+    }
+
+    /// Find fields matching the query inside the given struct. This returns a filtered
+    /// struct if one or more fields matched.
+    ///
+    /// This expects `self.depth` to point at the field term of the query.
+    fn search_struct(&self, item_struct: &ItemStruct) -> Vec<Found<'f>> {
+        // Past a field, the search continues in the definition of its type.
+        if !self.can_match() {
+            return self.search_field_types(&item_struct.fields, self.depth);
+        }
+
+        let fields = match filter_fields(&item_struct.fields, self.term(), self.filters()) {
+            Some(fields) => fields,
+            None => return Vec::new(),
+        };
+
+        let result = Item::Struct(ItemStruct {
+            fields,
+            ..item_struct.clone()
+        });
+
+        vec![Found::new(
+            Cow::Owned(result),
+            self.path.clone(),
+            self.field_siblings(&item_struct.fields, self.term()),
+        )]
+    }
+
+    /// Search the definitions of the types of the fields matching the term at `depth` for
+    /// the rest of the query, so that `Outer::0::1` finds the second field of the type of
+    /// `Outer`'s first field.
+    ///
+    /// Only structs and enums in the container the fields' parent was found in are searched,
+    /// and only for fields whose type is a plain path like `Inner` or `Inner<T>`.
+    ///
+    /// This expects `self.path` to end with the name of the fields' parent.
+    fn search_field_types(&self, fields: &Fields, depth: usize) -> Vec<Found<'f>> {
+        let term = self.query.part(depth);
+        let container = &self.path[..self.path.len().saturating_sub(1)];
+        let mut found = Vec::new();
+
+        for (index, field) in fields.iter().enumerate() {
+            if !term.matches_field(index, field) || !term.includes_position(index) {
+                continue;
+            }
+
+            let ty = match self_ty_ident(&field.ty) {
+                Some(ty) => ty,
+                None => continue,
+            };
+
+            for item in &self.scope {
+                if matches!(item, Item::Struct(_) | Item::Enum(_)) && item.name() == Some(ty) {
+                    found.extend(self.search_children_from(container.to_vec(), item, depth + 1));
+                }
+            }
+        }
+
+        found
+    }
+
+    /// Get the names of the members of a container that weren't matched, if this search is
+    /// collecting sibling names.
+    fn siblings<'i>(
+        &self,
+        names: impl IntoIterator<Item = Option<&'i Ident>>,
+        is_match: impl Fn(usize) -> bool,
+    ) -> Vec<Ident> {
+        if self.sibling_names.is_none() {
+            return Vec::new();
         }
 
-        if let Item::Trait(trait_item) = item {
+        names
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| !is_match(*index))
+            .filter_map(|(_, name)| name.cloned())
+            .collect()
+    }
+
+    /// Get the names of the fields that don't match `term`, if this search is collecting
+    /// sibling names.
+    fn field_siblings(&self, fields: &Fields, term: &SelectorSegment) -> Vec<Ident> {
+        let fields = fields.iter().collect::<Vec<_>>();
+        self.siblings(fields.iter().map(|field| field.ident.as_ref()), |index| {
+            field_matches(term, self.filters(), index, fields[index])
+        })
+    }
+
+    /// Apply attributes to the results and return them
+    fn with_attrs(mut self, attrs: Vec<Attribute>) -> Vec<Found<'f>> {
+        add_cfgs(&mut self.results, &attrs);
+        self.results
+    }
+
+    /// Get the path of an item in the container being searched.
+    fn path_to(&self, item: &Item) -> Vec<String> {
+        let mut path = self.path.clone();
+        path.extend(item.name().map(Ident::to_string));
+        path
+    }
+
+    /// Group results found inside `item` under a node for `item` when building a tree.
+    fn nest(&self, item: Cow<'f, Item>, found: Vec<Found<'f>>) -> Vec<Found<'f>> {
+        if !self.tree || found.is_empty() {
+            return found;
+        }
+
+        vec![Found {
+            children: found,
+            path: self.path_to(&item),
+            ..Found::new(item, Vec::new(), Vec::new())
+        }]
+    }
+}
+
+impl<'q, 'f> Walk<'q, 'f> {
+    /// Check the item at position `index` in its container against the current term,
+    /// then either record it as a result or search inside it for the next term.
+    fn search_item_at(&mut self, index: usize, item: &'f Item) {
+        // `**` matches no levels, so the next term is checked against this item, or
+        // matches this item and keeps going, so the next term is also sought inside it.
+        // Either way it reaches everything a restart would, so there's no need for one.
+        if self.term().is_any_depth() {
+            // Everything reachable from an item at this `**` was found when the item was first
+            // reached here, such as through another ancestor matching `_` in `**::_::**::E`.
+            let key = (item as *const Item as usize, self.depth);
+            if !self.visited.borrow_mut().insert(key) {
+                self.note(|| format!("{} was already searched for `**`", describe(item)));
+                return;
+            }
+
             self.depth += 1;
-            let new_matches = ItemTraitSearch::new(self).search(trait_item);
-            self.results.extend(new_matches);
+            self.search_item_at(index, item);
             self.depth -= 1;
+
+            // Consecutive `**` segments match the same levels, so only the last one descends;
+            // otherwise each level could be reached once per way of splitting the levels above.
+            if self.query.part(self.depth + 1).is_any_depth() {
+                return;
+            }
+
+            let new_matches = self.search_children(item, self.depth);
+            self.results
+                .extend(self.nest(Cow::Borrowed(item), new_matches));
             return;
         }
 
-        self.results
-            .extend(self.search_deeper(item).with_attrs(item.cfg_attrs()));
+        if self.is_match(index, item) {
+            // If we're on the last term of the path, we can go ahead and match
+            // right now.
+            if self.can_match() {
+                if self.accepts(item) {
+                    self.note(|| format!("{} matched `{}`", describe(item), self.term()));
+                    let siblings = match &self.sibling_names {
+                        Some(names) => {
+                            self.siblings(names.iter().map(Option::as_ref), |i| i == index)
+                        }
+                        None => Vec::new(),
+                    };
+                    self.results.push(Found::new(
+                        Cow::Borrowed(item),
+                        self.path_to(item),
+                        siblings,
+                    ));
+                } else {
+                    self.note(|| {
+                        format!(
+                            "{} matched `{}`, but its attributes didn't satisfy the filters",
+                            describe(item),
+                            self.term()
+                        )
+                    });
+                }
+            } else {
+                self.note(|| {
+                    format!(
+                        "{} matched `{}`, so searching inside it for `{}`",
+                        describe(item),
+                        self.term(),
+                        self.query.part(self.depth + 1)
+                    )
+                });
+                let new_matches = self.search_deeper(item);
+                if new_matches.is_empty() {
+                    self.note(|| {
+                        format!(
+                            "nothing inside {} matched `{}`",
+                            describe(item),
+                            self.query.part(self.depth + 1)
+                        )
+                    });
+                }
+
+                if !new_matches.is_empty()
+                    && self.query.options().stop_at_kinds.contains(&item.kind())
+                {
+                    self.note(|| format!("returning {} whole", describe(item)));
+                    self.results.push(Found::new(
+                        Cow::Borrowed(item),
+                        self.path_to(item),
+                        Vec::new(),
+                    ));
+                } else {
+                    self.results
+                        .extend(self.nest(Cow::Borrowed(item), new_matches));
+                }
+            }
+        }
+
+        if self.restarts() {
+            let new_matches = self.search_children(item, 0);
+            self.results
+                .extend(self.nest(Cow::Borrowed(item), new_matches));
+        }
     }
 }
 
-struct ItemTraitSearch<'a: 'b, 'b> {
-    search: &'b Search<'a>,
+struct ItemTraitSearch<'q, 'f, 'b> {
+    search: &'b Walk<'q, 'f>,
     trait_results: Vec<TraitItem>,
-    free_results: Vec<Item>,
+    /// The positions of the members in `trait_results`.
+    matched: Vec<usize>,
+    free_results: Vec<Found<'f>>,
 }
 
-impl<'a: 'b, 'b> ItemTraitSearch<'a, 'b> {
-    fn new(search: &'b Search<'a>) -> Self {
+impl<'q, 'f, 'b> ItemTraitSearch<'q, 'f, 'b> {
+    fn new(search: &'b Walk<'q, 'f>) -> Self {
         Self {
             search,
             trait_results: Vec::new(),
+            matched: Vec::new(),
             free_results: Vec::new(),
         }
     }
 
     /// Find items matching the provided query inside the given trait. This returns a filtered
-    /// impl if one or more items matched.
-    fn search(mut self, item_trait: &ItemTrait) -> Vec<Item> {
-        for item in &item_trait.items {
-            self.visit_trait_item(&item);
+    /// trait if one or more items matched.
+    ///
+    /// This expects the search's depth to point at the member term of the query; members that
+    /// are searched further go through `search_deeper`, which moves on to the next term.
+    ///
+    /// Items found inside members are given `cfgs`, the trait's `cfg` attributes, since
+    /// they're only compiled when the trait is.
+    fn search(mut self, item_trait: &ItemTrait, cfgs: &[Attribute]) -> Vec<Found<'f>> {
+        for (index, item) in item_trait.items.iter().enumerate() {
+            self.search_trait_item(index, item);
         }
 
+        add_cfgs(&mut self.free_results, cfgs);
+
         if self.trait_results.is_empty() {
             return self.free_results;
         }
 
+        let siblings = self
+            .search
+            .siblings(item_trait.items.iter().map(Name::name), |index| {
+                self.matched.contains(&index)
+            });
+
         let mut result = item_trait.clone();
         result.items = self.trait_results;
 
-        std::iter::once(Item::from(result))
-            .chain(self.free_results)
-            .collect()
+        std::iter::once(Found::new(
+            Cow::Owned(result.into()),
+            self.search.path.clone(),
+            siblings,
+        ))
+        .chain(self.free_results)
+        .collect()
     }
 }
 
-impl<'a, 'b, 'ast> Visit<'ast> for ItemTraitSearch<'a, 'b> {
-    fn visit_trait_item(&mut self, item: &TraitItem) {
-        // Return early if the name isn't a match.
-        if !item.is_named(self.search.term()) {
+impl ItemTraitSearch<'_, '_, '_> {
+    fn search_trait_item(&mut self, index: usize, item: &TraitItem) {
+        let options = self.search.query.options();
+        if self.search.restarts() && member_has_contents(trait_member_body(item), options) {
+            if let Some(child) = item.clone().to_item() {
+                self.free_results
+                    .extend(self.search.search_member(child, 0));
+            }
+        }
+
+        // Return early if the name or position isn't a match.
+        if !item.is_named(self.search.term()) || !self.search.term().includes_position(index) {
             return;
         }
 
         if self.search.can_match() {
+            if !self.search.passes_filters(item.attrs().unwrap_or_default()) {
+                return;
+            }
+
+            if let (TraitItem::Fn(method), Some(with_default)) =
+                (item, self.search.query.options().with_default)
+            {
+                if method.default.is_some() != with_default {
+                    return;
+                }
+            }
+
+            if self.search.query.options().methods_only
+                && !matches!(item, TraitItem::Fn(method) if method.sig.receiver().is_some())
+            {
+                return;
+            }
+
             // We've reached the end of the query path, so we should
             // register this trait item as a hit.
+            self.search.note(|| {
+                format!(
+                    "trait member {} matched `{}`",
+                    describe(item),
+                    self.search.term()
+                )
+            });
             self.trait_results.push(item.clone());
-        } else if let Some(child) = item.clone().to_item() {
+            self.matched.push(index);
+        } else if member_has_contents(trait_member_body(item), options) {
             // We haven't reached the end, but we can convert the trait
             // member into a free-standing item to continue the search.
-            let child_results = self.search.search_deeper(&child);
-            self.free_results.extend(child_results.results);
+            if let Some(child) = item.clone().to_item() {
+                self.free_results
+                    .extend(self.search.search_member(child, self.search.depth + 1));
+            }
         }
     }
 }
 
-fn contents_of_item(item: &Item) -> Vec<Item> {
-    match item {
-        Item::ExternCrate(_) => Vec::new(),
-        Item::Use(_) => Vec::new(),
-        Item::Static(_) => Vec::new(),
-        Item::Const(_) => Vec::new(),
-        Item::Fn(item_fn) => item_fn
+struct ItemImplSearch<'q, 'f, 'b> {
+    search: &'b Walk<'q, 'f>,
+    impl_results: Vec<ImplItem>,
+    /// The positions of the members in `impl_results`.
+    matched: Vec<usize>,
+    free_results: Vec<Found<'f>>,
+}
+
+impl<'q, 'f, 'b> ItemImplSearch<'q, 'f, 'b> {
+    fn new(search: &'b Walk<'q, 'f>) -> Self {
+        Self {
+            search,
+            impl_results: Vec::new(),
+            matched: Vec::new(),
+            free_results: Vec::new(),
+        }
+    }
+
+    /// Find items matching the provided query inside the given impl block. This returns a
+    /// filtered impl if one or more items matched.
+    ///
+    /// Like [`ItemTraitSearch::search`], this expects the search's depth to point at the
+    /// member term of the query, and gives items found inside members the impl's `cfgs`.
+    fn search(mut self, item_impl: &ItemImpl, cfgs: &[Attribute]) -> Vec<Found<'f>> {
+        for (index, item) in item_impl.items.iter().enumerate() {
+            self.search_impl_item(index, item);
+        }
+
+        add_cfgs(&mut self.free_results, cfgs);
+
+        if self.impl_results.is_empty() {
+            return self.free_results;
+        }
+
+        let siblings = self
+            .search
+            .siblings(item_impl.items.iter().map(Name::name), |index| {
+                self.matched.contains(&index)
+            });
+
+        let mut result = item_impl.clone();
+        result.items = self.impl_results;
+
+        std::iter::once(Found::new(
+            Cow::Owned(result.into()),
+            self.search.path.clone(),
+            siblings,
+        ))
+        .chain(self.free_results)
+        .collect()
+    }
+
+    fn search_impl_item(&mut self, index: usize, item: &ImplItem) {
+        let options = self.search.query.options();
+        if self.search.restarts() && member_has_contents(impl_member_body(item), options) {
+            if let Some(child) = item.clone().to_item() {
+                self.free_results
+                    .extend(self.search.search_member(child, 0));
+            }
+        }
+
+        if !item.is_named(self.search.term()) || !self.search.term().includes_position(index) {
+            return;
+        }
+
+        if self.search.can_match() {
+            if !self.search.passes_filters(item.attrs().unwrap_or_default()) {
+                return;
+            }
+
+            if self.search.query.options().methods_only
+                && !matches!(item, ImplItem::Fn(method) if method.sig.receiver().is_some())
+            {
+                return;
+            }
+
+            self.search.note(|| {
+                format!(
+                    "impl member {} matched `{}`",
+                    describe(item),
+                    self.search.term()
+                )
+            });
+            self.impl_results.push(item.clone());
+            self.matched.push(index);
+        } else if member_has_contents(impl_member_body(item), options) {
+            if let Some(child) = item.clone().to_item() {
+                self.free_results
+                    .extend(self.search.search_member(child, self.search.depth + 1));
+            }
+        }
+    }
+}
+
+/// Get the file path from a module's `#[path = "..."]` attribute, if it has one.
+fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(lit), ..
+            }) => Some(lit.value()),
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+/// Describe an item for a diagnostic, such as "`a` (mod)".
+fn describe(item: &impl Name) -> String {
+    match item.name() {
+        Some(name) => format!("`{}` ({})", name, item.kind()),
+        None => format!("an unnamed {}", item.kind()),
+    }
+}
+
+/// Get the name an impl block is matched by.
+///
+/// This is the ident of the self type when it's a plain path like `Foo` or `Foo<T>`.
+/// Other self types, such as `(A, B)` or `&T`, have no simple ident, so those impls
+/// are matched by the last segment of their trait's path instead.
+fn impl_name(item: &ItemImpl) -> Option<&Ident> {
+    self_ty_ident(&item.self_ty).or_else(|| trait_ident(item))
+}
+
+/// Get the last segment of the path of the trait an impl block implements, if any. An impl
+/// implements at most one trait, and inherent impls implement none.
+fn trait_ident(item: &ItemImpl) -> Option<&Ident> {
+    item.trait_
+        .as_ref()
+        .and_then(|(_, path, _)| path.segments.last())
+        .map(|seg| &seg.ident)
+}
+
+/// Get the names a `use` tree brings into scope, such as `Baz` for `foo::Bar as Baz` or `foo`
+/// for `foo::{self}`. Glob imports and renames to `_` don't contribute any names.
+fn imported_names(tree: &UseTree) -> impl Iterator<Item = &Ident> {
+    fn collect<'t>(tree: &'t UseTree, parent: Option<&'t Ident>, names: &mut Vec<&'t Ident>) {
+        match tree {
+            UseTree::Path(path) => collect(&path.tree, Some(&path.ident), names),
+            UseTree::Name(name) if name.ident == "self" => names.extend(parent),
+            UseTree::Name(name) => names.push(&name.ident),
+            UseTree::Rename(rename) if rename.rename == "_" => {}
+            UseTree::Rename(rename) => names.push(&rename.rename),
+            UseTree::Glob(_) => {}
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    collect(tree, parent, names);
+                }
+            }
+        }
+    }
+
+    let mut names = Vec::new();
+    collect(tree, None, &mut names);
+    names.into_iter()
+}
+
+/// Get the ident of the type an impl block is for, if it is a plain path like `Foo`
+/// or `Foo<T>`.
+fn self_ty_ident(ty: &Type) -> Option<&Ident> {
+    match ty {
+        Type::Path(TypePath { qself: None, path }) => path.segments.last().map(|seg| &seg.ident),
+        _ => None,
+    }
+}
+
+/// Check if the field at `index` in its parent matches `term` and satisfies `filters`.
+fn field_matches(term: &SelectorSegment, filters: &[Filter], index: usize, field: &Field) -> bool {
+    term.matches_field(index, field)
+        && term.includes_position(index)
+        && filters.iter().all(|filter| filter.matches(&field.attrs))
+}
+
+/// Remove the attributes of a result that the selector's options don't keep.
+pub(crate) fn retain_attrs<'f>(mut item: Cow<'f, Item>, options: &Options) -> Cow<'f, Item> {
+    let paths = match &options.keep_only_attrs {
+        Some(paths) => paths,
+        None => return item,
+    };
+
+    let is_kept = |attr: &Attribute| {
+        let path = attr
+            .path()
+            .segments
+            .iter()
+            .map(|segment| segment.ident.to_string())
+            .collect::<Vec<_>>()
+            .join("::");
+        paths.contains(&path)
+    };
+
+    if item.attrs().unwrap_or_default().iter().all(is_kept) {
+        return item;
+    }
+
+    if let Some(attrs) = item.to_mut().attrs_mut() {
+        attrs.retain(is_kept);
+    }
+
+    item
+}
+
+/// Get the span of an item from the end of its attributes to its end. The search may add
+/// attributes from enclosing items, such as their `cfg`s, which would stretch a span that
+/// included them back to where they were copied from.
+pub(crate) fn span_without_attrs(item: &Item) -> Span {
+    let mut item = item.clone();
+    if let Some(attrs) = item.attrs_mut() {
+        attrs.clear();
+    }
+
+    item.span()
+}
+
+/// Drop results nested more deeply than another result with the same name. Results without
+/// a name are kept.
+fn keep_outermost(results: &mut Vec<Found<'_>>) {
+    let mut shallowest = HashMap::new();
+    for_each_leaf(results, &mut |found| {
+        if let Some(name) = found.item.name() {
+            let depth = shallowest
+                .entry(name.to_string())
+                .or_insert(found.path.len());
+            *depth = (*depth).min(found.path.len());
+        }
+    });
+
+    retain_leaves(results, &mut |found| match found.item.name() {
+        Some(name) => shallowest[&name.to_string()] == found.path.len(),
+        None => true,
+    });
+}
+
+/// Put results in the order requested by the selector's options. The sort is stable, so
+/// results of the same kind stay in source order.
+pub(crate) fn sort_by_kind<T>(results: &mut [T], options: &Options, item: impl Fn(&T) -> &Item) {
+    if options.group_by_kind {
+        results.sort_by_key(|result| ItemKind::of(item(result)).group_rank());
+    }
+}
+
+/// Visit the results a flat search would return, which are the leaves of a tree of results.
+fn for_each_leaf<'r, 'f>(results: &'r [Found<'f>], visit: &mut impl FnMut(&'r Found<'f>)) {
+    for found in results {
+        if found.children.is_empty() {
+            visit(found);
+        } else {
+            for_each_leaf(&found.children, visit);
+        }
+    }
+}
+
+/// Keep the leaves of a tree of results for which `keep` returns true, dropping containers
+/// that are left without any leaves.
+fn retain_leaves<'f>(results: &mut Vec<Found<'f>>, keep: &mut impl FnMut(&Found<'f>) -> bool) {
+    results.retain_mut(|found| {
+        if found.children.is_empty() {
+            keep(found)
+        } else {
+            retain_leaves(&mut found.children, keep);
+            !found.children.is_empty()
+        }
+    });
+}
+
+/// Replace each leaf of a tree of results with the output of `map`.
+fn map_leaves<'f>(
+    results: Vec<Found<'f>>,
+    map: &mut impl FnMut(Found<'f>) -> Found<'f>,
+) -> Vec<Found<'f>> {
+    results
+        .into_iter()
+        .map(|mut found| {
+            if found.children.is_empty() {
+                map(found)
+            } else {
+                found.children = map_leaves(std::mem::take(&mut found.children), map);
+                found
+            }
+        })
+        .collect()
+}
+
+/// Give results found inside an item that item's `cfg` attributes.
+fn add_cfgs(results: &mut [Found<'_>], cfgs: &[Attribute]) {
+    if cfgs.is_empty() {
+        return;
+    }
+
+    for found in results {
+        found.add_attrs(cfgs);
+    }
+}
+
+/// Drop results that were already found, which happens when a selector with `**` reaches
+/// the same item by more than one path. Results borrowed from the searched file are the
+/// same if they're the same item; results the search created are compared by path and tokens.
+fn dedup_results(results: &mut Vec<Found<'_>>) {
+    let mut borrowed = HashSet::new();
+    let mut owned = HashSet::new();
+    retain_leaves(results, &mut |found| match &found.item {
+        Cow::Borrowed(item) => borrowed.insert(*item as *const Item),
+        Cow::Owned(item) => owned.insert((found.path.clone(), item.to_token_stream().to_string())),
+    });
+}
+
+/// Get the names of the items in a container, by position.
+fn names_of<'i>(items: impl IntoIterator<Item = &'i Item>) -> Vec<Option<Ident>> {
+    items.into_iter().map(|item| item.name().cloned()).collect()
+}
+
+/// Get a copy of `fields` containing only the fields that match `term` and satisfy `filters`,
+/// or `None` if no fields match.
+fn filter_fields(fields: &Fields, term: &SelectorSegment, filters: &[Filter]) -> Option<Fields> {
+    let filter = |fields: &Punctuated<Field, Comma>| -> Punctuated<Field, Comma> {
+        fields
+            .iter()
+            .enumerate()
+            .filter(|(index, field)| field_matches(term, filters, *index, field))
+            .map(|(_, field)| field.clone())
+            .collect()
+    };
+
+    let filtered = match fields {
+        Fields::Named(named) => Fields::Named(FieldsNamed {
+            named: filter(&named.named),
+            ..named.clone()
+        }),
+        Fields::Unnamed(unnamed) => Fields::Unnamed(FieldsUnnamed {
+            unnamed: filter(&unnamed.unnamed),
+            ..unnamed.clone()
+        }),
+        Fields::Unit => Fields::Unit,
+    };
+
+    if filtered.is_empty() {
+        None
+    } else {
+        Some(filtered)
+    }
+}
+
+/// Get every item in a file, including items nested in modules, function bodies, traits
+/// and impls, with each container before the items inside it.
+pub(crate) fn descendants(file: &syn::File) -> Vec<Cow<'_, Item>> {
+    fn visit<'f>(item: Cow<'f, Item>, options: &Options, out: &mut Vec<Cow<'f, Item>>) {
+        let contents = match &item {
+            Cow::Borrowed(item) => contents_of_item(item, options),
+            Cow::Owned(item) => contents_of_item(item, options)
+                .into_iter()
+                .map(|member| Cow::Owned(member.into_owned()))
+                .collect(),
+        };
+
+        out.push(item);
+        for member in contents {
+            visit(member, options, out);
+        }
+    }
+
+    let options = Options::default();
+    let mut items = Vec::new();
+    for item in top_level_items(file) {
+        visit(item, &options, &mut items);
+    }
+
+    items
+}
+
+/// Get the items inside `item`, as a search with default options would see them.
+pub(crate) fn children_of(item: &Item) -> Vec<Cow<'_, Item>> {
+    contents_of_item(item, &Options::default())
+}
+
+/// Get the top-level items of a file, including those declared in anonymous consts.
+pub(crate) fn top_level_items(file: &syn::File) -> Vec<Cow<'_, Item>> {
+    with_anonymous_const_items(file.items.iter().map(Cow::Borrowed).collect())
+}
+
+/// Add the items declared in the initializers of anonymous consts, such as
+/// `const _: () = { impl Foo {} };`, right after each const.
+///
+/// Anonymous consts can't be named in a path, so the items in them are treated as members
+/// of the container the const is declared in. That's also how they behave in Rust, where an
+/// impl in an anonymous const applies to its type wherever it's used.
+fn with_anonymous_const_items(contents: Vec<Cow<'_, Item>>) -> Vec<Cow<'_, Item>> {
+    let mut expanded = Vec::with_capacity(contents.len());
+    for item in contents {
+        let nested = match &item {
+            Cow::Borrowed(Item::Const(item_const)) if item_const.ident == "_" => {
+                block_items(item_const)
+                    .into_iter()
+                    .map(Cow::Borrowed)
+                    .collect()
+            }
+            Cow::Owned(Item::Const(item_const)) if item_const.ident == "_" => {
+                block_items(item_const)
+                    .into_iter()
+                    .cloned()
+                    .map(Cow::Owned)
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        expanded.push(item);
+        expanded.extend(with_anonymous_const_items(nested));
+    }
+
+    expanded
+}
+
+/// Get the items declared in the block a const is initialized with, such as `S` in
+/// `const X: () = { struct S; };`.
+fn block_items(item_const: &ItemConst) -> Vec<&Item> {
+    match &*item_const.expr {
+        Expr::Block(block) => block
             .block
             .stmts
             .iter()
-            .cloned()
-            .filter_map(Stmt::to_item)
+            .filter_map(|stmt| match stmt {
+                Stmt::Item(item) => Some(item),
+                _ => None,
+            })
             .collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Get the items inside `item`. Items declared inside it are borrowed, while trait and impl
+/// members have to be converted to free-standing items.
+///
+/// The items of anonymous consts in `item` are included, and an anonymous const itself has
+/// no contents, since its items already belong to its container.
+fn contents_of_item<'f>(item: &'f Item, options: &Options) -> Vec<Cow<'f, Item>> {
+    with_anonymous_const_items(match item {
+        Item::ExternCrate(_) => Vec::new(),
+        Item::Use(_) => Vec::new(),
+        Item::Static(_) => Vec::new(),
+        Item::Const(item_const) if item_const.ident == "_" => Vec::new(),
+        Item::Const(item_const) => block_items(item_const)
+            .into_iter()
+            .map(Cow::Borrowed)
+            .collect(),
+        Item::Fn(item_fn) => stmt_items(&item_fn.block.stmts, options),
         Item::Mod(item_mod) => match &item_mod.content {
-            Some((_, nested)) => nested.clone(),
+            Some((_, nested)) => nested.iter().map(Cow::Borrowed).collect(),
             None => Vec::new(),
         },
         Item::ForeignMod(_) => Vec::new(),
@@ -243,12 +1668,92 @@ fn contents_of_item(item: &Item) -> Vec<Item> {
             .iter()
             .cloned()
             .filter_map(TraitItem::to_item)
+            .map(Cow::Owned)
             .collect(),
         Item::TraitAlias(_) => Vec::new(),
-        Item::Impl(_) => Vec::new(),
+        Item::Impl(item_impl) => item_impl
+            .items
+            .iter()
+            .cloned()
+            .filter_map(ImplItem::to_item)
+            .map(Cow::Owned)
+            .collect(),
         Item::Macro(_) => Vec::new(),
         Item::Verbatim(_) => Vec::new(),
         _ => Vec::new(),
+    })
+}
+
+/// Get the items declared in a function body, including those in `unsafe`, `async` and
+/// plain blocks that are statements of the body.
+fn stmt_items<'f>(stmts: &'f [Stmt], options: &Options) -> Vec<Cow<'f, Item>> {
+    stmts
+        .iter()
+        .flat_map(|stmt| match stmt {
+            Stmt::Local(local) if options.search_locals => {
+                let mut nested = NestedItems::default();
+                nested.visit_local(local);
+                nested.0.into_iter().map(Cow::Borrowed).collect()
+            }
+            Stmt::Item(item) => vec![Cow::Borrowed(item)],
+            Stmt::Expr(expr, _) => match inner_block(expr) {
+                Some(block) => stmt_items(&block.stmts, options),
+                None => Vec::new(),
+            },
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+/// Get the block of an `unsafe`, `async` or plain block expression, whose items are in the
+/// scope of the enclosing function.
+fn inner_block(expr: &Expr) -> Option<&Block> {
+    match expr {
+        Expr::Block(expr) => Some(&expr.block),
+        Expr::Unsafe(expr) => Some(&expr.block),
+        Expr::Async(expr) => Some(&expr.block),
+        _ => None,
+    }
+}
+
+/// Get the block of a trait member that can declare items, such as a method's default body.
+fn trait_member_body(member: &TraitItem) -> Option<&Block> {
+    match member {
+        TraitItem::Fn(method) => method.default.as_ref(),
+        TraitItem::Const(item) => match &item.default {
+            Some((_, Expr::Block(block))) => Some(&block.block),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Get the block of an impl member that can declare items, such as a method's body.
+fn impl_member_body(member: &ImplItem) -> Option<&Block> {
+    match member {
+        ImplItem::Fn(method) => Some(&method.block),
+        ImplItem::Const(item) => match &item.expr {
+            Expr::Block(block) => Some(&block.block),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Check if a trait or impl member's body declares any items, so converting the member to a
+/// free-standing item to search inside it is worthwhile.
+fn member_has_contents(body: Option<&Block>, options: &Options) -> bool {
+    body.is_some_and(|block| !stmt_items(&block.stmts, options).is_empty())
+}
+
+/// Collects the outermost items declared anywhere inside an expression or statement.
+#[derive(Default)]
+struct NestedItems<'ast>(Vec<&'ast Item>);
+
+impl<'ast> Visit<'ast> for NestedItems<'ast> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        // Items inside this one belong to it, so don't descend any further.
+        self.0.push(item);
     }
 }
 
@@ -271,31 +1776,35 @@ impl Name for Item {
             Item::Union(item) => Some(&item.ident),
             Item::Trait(item) => Some(&item.ident),
             Item::TraitAlias(item) => Some(&item.ident),
-            Item::Impl(_) => None,
+            Item::Impl(item) => impl_name(item),
             Item::Macro(item) => item.ident.as_ref(),
             Item::Verbatim(_) => None,
             _ => None,
         }
     }
+
+    fn kind(&self) -> ItemKind {
+        ItemKind::of(self)
+    }
 }
 
 impl Attrs for Item {
     fn attrs(&self) -> Option<&[Attribute]> {
         match self {
             Item::ExternCrate(item) => Some(&item.attrs),
-            Item::Use(_) => None,
+            Item::Use(item) => Some(&item.attrs),
             Item::Static(item) => Some(&item.attrs),
             Item::Const(item) => Some(&item.attrs),
             Item::Fn(item) => Some(&item.attrs),
             Item::Mod(item) => Some(&item.attrs),
-            Item::ForeignMod(_) => None,
+            Item::ForeignMod(item) => Some(&item.attrs),
             Item::Type(item) => Some(&item.attrs),
             Item::Struct(item) => Some(&item.attrs),
             Item::Enum(item) => Some(&item.attrs),
             Item::Union(item) => Some(&item.attrs),
             Item::Trait(item) => Some(&item.attrs),
             Item::TraitAlias(item) => Some(&item.attrs),
-            Item::Impl(_) => None,
+            Item::Impl(item) => Some(&item.attrs),
             Item::Macro(item) => Some(&item.attrs),
             Item::Verbatim(_) => None,
             _ => None,
@@ -305,19 +1814,19 @@ impl Attrs for Item {
     fn attrs_mut(&mut self) -> Option<&mut Vec<Attribute>> {
         match self {
             Item::ExternCrate(item) => Some(&mut item.attrs),
-            Item::Use(_) => None,
+            Item::Use(item) => Some(&mut item.attrs),
             Item::Static(item) => Some(&mut item.attrs),
             Item::Const(item) => Some(&mut item.attrs),
             Item::Fn(item) => Some(&mut item.attrs),
             Item::Mod(item) => Some(&mut item.attrs),
-            Item::ForeignMod(_) => None,
+            Item::ForeignMod(item) => Some(&mut item.attrs),
             Item::Type(item) => Some(&mut item.attrs),
             Item::Struct(item) => Some(&mut item.attrs),
             Item::Enum(item) => Some(&mut item.attrs),
             Item::Union(item) => Some(&mut item.attrs),
             Item::Trait(item) => Some(&mut item.attrs),
             Item::TraitAlias(item) => Some(&mut item.attrs),
-            Item::Impl(_) => None,
+            Item::Impl(item) => Some(&mut item.attrs),
             Item::Macro(item) => Some(&mut item.attrs),
             Item::Verbatim(_) => None,
             _ => None,
@@ -336,6 +1845,41 @@ impl Name for TraitItem {
             _ => None,
         }
     }
+
+    fn kind(&self) -> ItemKind {
+        match self {
+            TraitItem::Fn(_) => ItemKind::Fn,
+            TraitItem::Const(_) => ItemKind::Const,
+            TraitItem::Type(_) => ItemKind::Type,
+            TraitItem::Macro(_) => ItemKind::Macro,
+            TraitItem::Verbatim(_) => ItemKind::Verbatim,
+            _ => ItemKind::Verbatim,
+        }
+    }
+}
+
+impl Attrs for TraitItem {
+    fn attrs(&self) -> Option<&[Attribute]> {
+        match self {
+            TraitItem::Fn(item) => Some(&item.attrs),
+            TraitItem::Const(item) => Some(&item.attrs),
+            TraitItem::Type(item) => Some(&item.attrs),
+            TraitItem::Macro(item) => Some(&item.attrs),
+            TraitItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
+
+    fn attrs_mut(&mut self) -> Option<&mut Vec<Attribute>> {
+        match self {
+            TraitItem::Fn(item) => Some(&mut item.attrs),
+            TraitItem::Const(item) => Some(&mut item.attrs),
+            TraitItem::Type(item) => Some(&mut item.attrs),
+            TraitItem::Macro(item) => Some(&mut item.attrs),
+            TraitItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
 }
 
 impl TryToItem for TraitItem {
@@ -353,10 +1897,13 @@ impl TryToItem for TraitItem {
                 expr: Box::new(item.default?.1),
                 semi_token: item.semi_token,
             })),
+            // A method without a default body has nothing inside it to search, and isn't a
+            // function that could stand on its own, so it has no free-standing form. It's
+            // still matched as a member of its trait.
             TraitItem::Fn(item) => Some(Item::Fn(ItemFn {
                 attrs: item.attrs,
                 vis: Visibility::Inherited,
-                sig: item.sig,
+                sig: without_receiver(item.sig),
                 block: Box::new(item.default?),
             })),
             TraitItem::Type(item) => Some(Item::Type(ItemType {
@@ -376,6 +1923,103 @@ impl TryToItem for TraitItem {
     }
 }
 
+impl Name for ImplItem {
+    fn name(&self) -> Option<&Ident> {
+        match self {
+            ImplItem::Fn(item) => Some(&item.sig.ident),
+            ImplItem::Const(item) => Some(&item.ident),
+            ImplItem::Type(item) => Some(&item.ident),
+            ImplItem::Macro(_) => None,
+            ImplItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
+
+    fn kind(&self) -> ItemKind {
+        match self {
+            ImplItem::Fn(_) => ItemKind::Fn,
+            ImplItem::Const(_) => ItemKind::Const,
+            ImplItem::Type(_) => ItemKind::Type,
+            ImplItem::Macro(_) => ItemKind::Macro,
+            ImplItem::Verbatim(_) => ItemKind::Verbatim,
+            _ => ItemKind::Verbatim,
+        }
+    }
+}
+
+impl Attrs for ImplItem {
+    fn attrs(&self) -> Option<&[Attribute]> {
+        match self {
+            ImplItem::Fn(item) => Some(&item.attrs),
+            ImplItem::Const(item) => Some(&item.attrs),
+            ImplItem::Type(item) => Some(&item.attrs),
+            ImplItem::Macro(item) => Some(&item.attrs),
+            ImplItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
+
+    fn attrs_mut(&mut self) -> Option<&mut Vec<Attribute>> {
+        match self {
+            ImplItem::Fn(item) => Some(&mut item.attrs),
+            ImplItem::Const(item) => Some(&mut item.attrs),
+            ImplItem::Type(item) => Some(&mut item.attrs),
+            ImplItem::Macro(item) => Some(&mut item.attrs),
+            ImplItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
+}
+
+impl TryToItem for ImplItem {
+    fn to_item(self) -> Option<Item> {
+        match self {
+            ImplItem::Const(item) => Some(Item::Const(ItemConst {
+                attrs: item.attrs,
+                vis: item.vis,
+                const_token: item.const_token,
+                ident: item.ident,
+                generics: item.generics,
+                colon_token: item.colon_token,
+                ty: Box::new(item.ty),
+                eq_token: item.eq_token,
+                expr: Box::new(item.expr),
+                semi_token: item.semi_token,
+            })),
+            ImplItem::Fn(item) => Some(Item::Fn(ItemFn {
+                attrs: item.attrs,
+                vis: item.vis,
+                sig: without_receiver(item.sig),
+                block: Box::new(item.block),
+            })),
+            ImplItem::Type(item) => Some(Item::Type(ItemType {
+                attrs: item.attrs,
+                vis: item.vis,
+                type_token: item.type_token,
+                ident: item.ident,
+                generics: item.generics,
+                eq_token: item.eq_token,
+                ty: Box::new(item.ty),
+                semi_token: item.semi_token,
+            })),
+            ImplItem::Macro(_) => None,
+            ImplItem::Verbatim(_) => None,
+            _ => None,
+        }
+    }
+}
+
+/// Remove the `self` parameter from a method's signature, since a free-standing function
+/// can't have one. Uses of `self` or `Self` in the body are left as they are.
+fn without_receiver(mut sig: Signature) -> Signature {
+    sig.inputs = sig
+        .inputs
+        .into_iter()
+        .filter(|input| !matches!(input, FnArg::Receiver(_)))
+        .collect();
+    sig
+}
+
 impl TryToItem for Stmt {
     fn to_item(self) -> Option<Item> {
         if let Stmt::Item(item) = self {
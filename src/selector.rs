@@ -1,17 +1,33 @@
 //! The selector for a given search, with its trait implementations.
 
-use crate::search::Search;
-use crate::Error;
+use crate::filter::Filter;
+use crate::search::{retain_attrs, sort_by_kind, top_level_items, Walk};
+use crate::{Diagnostic, Error, ItemKind, Match};
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::HashMap;
 use std::fmt;
 use std::str::FromStr;
+use std::sync::Arc;
+use syn::ext::IdentExt;
 use syn::{Ident, Item};
 
 /// The path provided by the user to search for.
 ///
 /// Not all Rust paths are valid selectors; UFCS and generics are not supported.
+///
+/// Selectors are compared and ordered by their segments, one at a time, so they can be kept
+/// in a `BTreeSet` or sorted `Vec`. Within a segment, idents sort first, then field
+/// positions, glob patterns, `_` wildcards, `*`, `**` and finally `{a, b}` name lists.
+/// Selectors with the same segments are then ordered by their options, such as
+/// [`Selector::anchored`] and attribute filters. Module resolvers can't be compared, so
+/// they're ignored: two selectors that differ only in their resolver are equal.
 #[derive(Debug, Clone)]
 pub struct Selector {
     segments: Vec<SelectorSegment>,
+    mod_resolver: Option<ModResolver>,
+    options: Options,
 }
 
 impl Selector {
@@ -27,11 +43,853 @@ impl Selector {
         s.as_ref().parse()
     }
 
+    /// Parse a path that may end with `::`, as typed into an autocomplete box.
+    ///
+    /// A trailing `::` makes the path a prefix query: the returned selector matches every
+    /// direct child of the items the rest of the path matches, and the returned flag is `true`.
+    /// Other paths are parsed as [`Selector::try_from`] would, with the flag set to `false`.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a { fn b() {} struct C; }").unwrap();
+    /// let (selector, is_prefix) = Selector::parse_prefix("a::").unwrap();
+    /// assert!(is_prefix);
+    /// assert_eq!(selector.apply_to(&file).len(), 2);
+    /// ```
+    pub fn parse_prefix(s: impl AsRef<str>) -> Result<(Self, bool), Error> {
+        let s = s.as_ref();
+        match s.strip_suffix("::") {
+            Some(prefix) => {
+                let mut selector = Self::try_from(prefix)?;
+                selector.segments.push(SelectorSegment::Wildcard {
+                    kind: None,
+                    range: None,
+                });
+                Ok((selector, true))
+            }
+            None => Ok((Self::try_from(s)?, false)),
+        }
+    }
+
+    /// Register a callback to load the contents of modules declared in another file,
+    /// such as `mod foo;`.
+    ///
+    /// When the search needs to look inside such a module, the callback is invoked with
+    /// the names of the items from the root of the searched file to the module, inclusive.
+    /// If it returns a file, that file's items are searched as the module's contents.
+    /// A module whose `#[path = "..."]` attribute names a file that's already being searched
+    /// further up the same path isn't loaded again, so circular module declarations don't
+    /// recurse forever. Modules without the attribute can't form a cycle, as long as the
+    /// callback loads each module path from its own file.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a;").unwrap();
+    /// let selector = Selector::try_from("a::B")
+    ///     .unwrap()
+    ///     .with_mod_resolver(|path| match path {
+    ///         [a] if a == "a" => syn::parse_str("struct B;").ok(),
+    ///         _ => None,
+    ///     });
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn with_mod_resolver(
+        self,
+        resolver: impl Fn(&[String]) -> Option<syn::File> + Send + Sync + 'static,
+    ) -> Self {
+        self.with_mod_path_resolver(move |path, _| resolver(path))
+    }
+
+    /// Register a callback to load the contents of modules declared in another file, like
+    /// [`Selector::with_mod_resolver`], that is also given the value of the module's
+    /// `#[path = "..."]` attribute, if it has one.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(r#"#[path = "imp/unix.rs"] mod imp;"#).unwrap();
+    /// let selector = Selector::try_from("imp::B")
+    ///     .unwrap()
+    ///     .with_mod_path_resolver(|_, file_path| match file_path {
+    ///         Some("imp/unix.rs") => syn::parse_str("struct B;").ok(),
+    ///         _ => None,
+    ///     });
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn with_mod_path_resolver(
+        mut self,
+        resolver: impl Fn(&[String], Option<&str>) -> Option<syn::File> + Send + Sync + 'static,
+    ) -> Self {
+        self.mod_resolver = Some(ModResolver(Arc::new(resolver)));
+        self
+    }
+
+    /// Also search for items declared inside the initializers of `let` bindings in function
+    /// bodies, such as `let f = || { struct Inner; };`.
+    ///
+    /// This is off by default, since items in closures and other expressions are rarely
+    /// what callers mean by a path and can produce surprising matches.
+    pub fn search_locals(mut self, search_locals: bool) -> Self {
+        self.options.search_locals = search_locals;
+        self
+    }
+
+    /// Set whether the first segment of this selector must match a top-level item.
+    ///
+    /// An unanchored selector can start matching at any depth, so `b::C` would also match
+    /// `a::b::C` and `a::f::b::C`. Selectors are anchored by default.
+    pub fn anchored(mut self, anchored: bool) -> Self {
+        self.options.anchored = anchored;
+        self
+    }
+
+    /// Set whether to keep only the shallowest results of each name, dropping results with the
+    /// same name that were found nested more deeply, such as a struct declared in a function
+    /// body that shares its name with a struct declared at module level.
+    ///
+    /// This is a heuristic for finding "the real definition" with an unanchored selector or
+    /// wildcards. Results without a name, such as unnamed impl blocks, are always kept. This
+    /// is off by default.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("struct E; fn f() { struct E; }").unwrap();
+    /// let selector = Selector::try_from("E").unwrap().anchored(false);
+    /// assert_eq!(selector.apply_to(&file).len(), 2);
+    /// assert_eq!(selector.outermost_only(true).apply_to(&file).len(), 1);
+    /// ```
+    pub fn outermost_only(mut self, outermost_only: bool) -> Self {
+        self.options.outermost_only = outermost_only;
+        self
+    }
+
+    /// Remove every attribute from the results except those with one of the given paths,
+    /// such as `["derive", "cfg", "doc"]` to drop noise like `#[allow(...)]` when showing
+    /// results as documentation. A path with several segments, like `serde::skip`, is
+    /// written with `::`.
+    ///
+    /// This only changes the attributes of the results themselves, not of items inside them.
+    /// All attributes are kept by default.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use quote::ToTokens;
+    /// use syn_select::Selector;
+    /// let file: syn::File =
+    ///     syn::parse_str("#[derive(Debug)] #[allow(dead_code)] struct A;").unwrap();
+    /// let results = Selector::try_from("A")
+    ///     .unwrap()
+    ///     .keep_only_attrs(&["derive"])
+    ///     .apply_to(&file);
+    /// assert_eq!(
+    ///     results[0].to_token_stream().to_string(),
+    ///     "# [derive (Debug)] struct A ;"
+    /// );
+    /// ```
+    pub fn keep_only_attrs(mut self, paths: &[&str]) -> Self {
+        self.options.keep_only_attrs = Some(paths.iter().map(|path| path.to_string()).collect());
+        self
+    }
+
+    /// Set whether [`Selector::apply_to_with_cfgs`] merges results that share a path but are
+    /// declared under different `cfg` predicates, such as a struct with one definition for
+    /// each platform, into one [`Match`] that lists each definition as a variant.
+    ///
+    /// This is off by default, which keeps each definition as a separate result.
+    pub fn merge_cfg_variants(mut self, merge_cfg_variants: bool) -> Self {
+        self.options.merge_cfg_variants = merge_cfg_variants;
+        self
+    }
+
+    /// Create an unanchored `Selector` that matches items whose path ends with `path`,
+    /// regardless of what they're nested in.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a { fn b() { struct Foo; } } struct Foo;").unwrap();
+    /// let selector = Selector::ends_with("Foo").unwrap();
+    /// assert_eq!(selector.apply_to(&file).len(), 2);
+    /// ```
+    pub fn ends_with(path: impl AsRef<str>) -> Result<Self, Error> {
+        Ok(Self::try_from(path)?.anchored(false))
+    }
+
+    /// Set whether `extern crate` items are matched by the name of the crate, rather than
+    /// by the name they're bound to in this file.
+    ///
+    /// By default, `extern crate foo as bar;` is matched by `bar`. With this set, it's
+    /// matched by `foo` instead, regardless of any local rename.
+    pub fn extern_crate_original_name(mut self, original_name: bool) -> Self {
+        self.options.extern_crate_original_name = original_name;
+        self
+    }
+
+    /// Set whether `pub use` re-exports are matched by the names they bring into scope.
+    ///
+    /// By default, `use` items have no name and are only found by kind, e.g. `_use`. With this
+    /// set, `pub use foo::Bar;` is matched by `Bar`, and `pub use foo::{Bar as Baz, Qux};` by
+    /// either `Baz` or `Qux`; the result is the whole `use` item. Glob imports don't bring in
+    /// any names of their own, and `use` items that aren't `pub` are only matched by name with
+    /// [`Selector::match_imports`].
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File =
+    ///     syn::parse_str("mod a { pub use inner::Bar; use inner::Qux; }").unwrap();
+    /// let selector = Selector::try_from("a::Bar").unwrap().match_reexports(true);
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn match_reexports(mut self, match_reexports: bool) -> Self {
+        self.options.match_reexports = match_reexports;
+        self
+    }
+
+    /// Set whether all `use` items are matched by the names they bring into scope.
+    ///
+    /// This is like [`Selector::match_reexports`], but also matches private imports, so
+    /// `use a::b::C;` is matched by `C`, `use a::b::C as D;` by `D`, and `use a::{B, C};` by
+    /// either `B` or `C`. Glob imports such as `use a::*;` don't bring in any names of their
+    /// own, so they are only found by a wildcard of their kind, such as `_use`.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File =
+    ///     syn::parse_str("mod a { use inner::Bar; use inner::{Qux as Bar, Baz}; }").unwrap();
+    /// let selector = Selector::try_from("a::Bar").unwrap().match_imports(true);
+    /// assert_eq!(selector.apply_to(&file).len(), 2);
+    /// ```
+    pub fn match_imports(mut self, match_imports: bool) -> Self {
+        self.options.match_imports = match_imports;
+        self
+    }
+
+    /// Only match items with an attribute argument that has the given value.
+    ///
+    /// The last segment of `path` names the argument inside an attribute's list, so
+    /// `with_attr_arg("serde::rename", "x")` matches items with `#[serde(rename = "x")]`. The
+    /// whole path is also checked against attributes of the form `#[path = value]`, so
+    /// `with_attr_arg("must_use", "reason")` matches `#[must_use = "reason"]`. String values are
+    /// compared by their contents; other values are compared by their tokens.
+    ///
+    /// This applies to the items at the end of the path, including enum variants and fields.
+    /// Calling it several times requires all the arguments to be present.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(r#"
+    ///     #[serde(rename = "x")]
+    ///     struct A;
+    ///     #[serde(rename = "y")]
+    ///     struct B;
+    /// "#).unwrap();
+    /// let selector = Selector::try_from("_").unwrap().with_attr_arg("serde::rename", "x");
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn with_attr_arg(mut self, path: &str, value: &str) -> Self {
+        self.options.filters.push(Filter::AttrArg {
+            path: path.split("::").map(|seg| seg.trim().to_string()).collect(),
+            value: value.to_string(),
+        });
+        self
+    }
+
+    /// Only match items that derive the named trait in a `#[derive(...)]` attribute.
+    ///
+    /// Traits are compared by the last segment of their path, so `Serialize` matches both
+    /// `#[derive(Serialize)]` and `#[derive(serde::Serialize)]`. A qualified `trait_name` is
+    /// reduced to its last segment the same way. Combined with a wildcard, this lists every
+    /// type that derives the trait.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(r#"
+    ///     #[derive(Debug, serde::Serialize)]
+    ///     struct A;
+    ///     #[derive(Debug)]
+    ///     struct B;
+    /// "#).unwrap();
+    /// let selector = Selector::try_from("_").unwrap().derives("Serialize");
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn derives(mut self, trait_name: &str) -> Self {
+        let name = trait_name.rsplit("::").next().unwrap_or(trait_name).trim();
+        self.options.filters.push(Filter::Derives(name.to_string()));
+        self
+    }
+
+    /// Only match items marked `#[automatically_derived]`, as derive macros mark the impls they
+    /// generate, or with `false`, only those that aren't. Both are matched by default.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(r#"
+    ///     #[automatically_derived]
+    ///     impl Clone for A { fn clone(&self) -> Self { A } }
+    ///     impl A { fn new() -> Self { A } }
+    /// "#).unwrap();
+    /// let selector = Selector::try_from("A").unwrap().automatically_derived(false);
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn automatically_derived(mut self, generated: bool) -> Self {
+        self.options
+            .filters
+            .push(Filter::AutomaticallyDerived(generated));
+        self
+    }
+
+    /// Rewrite the start of this selector using a table of path aliases, such as the public
+    /// paths of items re-exported from somewhere else in a crate.
+    ///
+    /// If a key of `aliases` is a prefix of this selector, that prefix is replaced by the
+    /// key's value; when several keys match, the longest one wins. The rewrite happens once,
+    /// so values aren't looked up again. Only exact, non-wildcard keys are supported: a key
+    /// containing a wildcard or position never matches. This returns an error if a key or
+    /// value isn't a valid selector.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use std::collections::HashMap;
+    /// use syn_select::Selector;
+    /// let mut aliases = HashMap::new();
+    /// aliases.insert("api::Client".to_string(), "imp::client::Client".to_string());
+    /// let selector = Selector::try_from("api::Client::send")
+    ///     .unwrap()
+    ///     .with_aliases(aliases)
+    ///     .unwrap();
+    /// assert_eq!(selector.to_string(), "imp::client::Client::send");
+    /// ```
+    pub fn with_aliases(mut self, aliases: HashMap<String, String>) -> Result<Self, Error> {
+        let mut best: Option<(usize, Vec<SelectorSegment>)> = None;
+        for (from, to) in &aliases {
+            let from = Selector::try_from(from)?.segments;
+            let to = Selector::try_from(to)?.segments;
+
+            let is_prefix = from.len() <= self.segments.len()
+                && from.iter().zip(&self.segments).all(|pair| match pair {
+                    (SelectorSegment::Ident(alias), SelectorSegment::Ident(own)) => alias == own,
+                    _ => false,
+                });
+
+            if is_prefix && best.as_ref().map_or(true, |(len, _)| from.len() > *len) {
+                best = Some((from.len(), to));
+            }
+        }
+
+        if let Some((len, to)) = best {
+            self.segments.splice(..len, to);
+        }
+
+        Ok(self)
+    }
+
+    /// Drop the first segment of this selector if it's the name of the crate being searched,
+    /// as in paths copied from rustdoc such as `mycrate::a::b::C`.
+    ///
+    /// Hyphens in `crate_name` are treated as underscores, so a package name from
+    /// `Cargo.toml` can be passed as is. The selector is unchanged if its first segment is
+    /// anything else, or if the crate name is its only segment.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let selector = Selector::try_from("my_crate::a::B")
+    ///     .unwrap()
+    ///     .strip_crate_prefix("my-crate");
+    /// assert_eq!(selector.to_string(), "a::B");
+    /// ```
+    pub fn strip_crate_prefix(mut self, crate_name: &str) -> Self {
+        let crate_name = crate_name.replace('-', "_");
+        if self.segments.len() > 1
+            && matches!(&self.segments[0], SelectorSegment::Ident(first) if *first == crate_name)
+        {
+            self.segments.remove(0);
+        }
+
+        self
+    }
+
+    /// Only match trait methods that have a default body, or with `false`, only those that
+    /// don't. Other results, including methods in impl blocks, aren't affected.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("trait T { fn a(); fn b() {} fn c(); }").unwrap();
+    /// let selector = Selector::try_from("T::_").unwrap().with_default(false);
+    /// match &selector.apply_to(&file)[0] {
+    ///     syn::Item::Trait(item) => assert_eq!(item.items.len(), 2),
+    ///     _ => panic!("Expected a trait"),
+    /// }
+    /// ```
+    pub fn with_default(mut self, with_default: bool) -> Self {
+        self.options.with_default = Some(with_default);
+        self
+    }
+
+    /// Return a container of the given kind whole when the rest of the path matches inside it,
+    /// instead of descending into it. Calling this again adds another kind.
+    ///
+    /// With [`ItemKind::Trait`], `a::b::C::d` returns all of the trait `C`, as long as it has a
+    /// member `d`, rather than `C` filtered down to `d`.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::{ItemKind, Selector};
+    /// let file: syn::File = syn::parse_str("trait C { fn d(); fn e(); }").unwrap();
+    /// let selector = Selector::try_from("C::d")
+    ///     .unwrap()
+    ///     .stop_at_kind(ItemKind::Trait);
+    /// match &selector.apply_to(&file)[0] {
+    ///     syn::Item::Trait(item) => assert_eq!(item.items.len(), 2),
+    ///     _ => panic!("Expected a trait"),
+    /// }
+    /// ```
+    pub fn stop_at_kind(mut self, kind: ItemKind) -> Self {
+        self.options.stop_at_kinds.push(kind);
+        self
+    }
+
+    /// Only match `macro_rules!` definitions exported with `#[macro_export]`, or with `false`,
+    /// only those that aren't. Other results, including macro invocations, aren't affected.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(
+    ///     "#[macro_export] macro_rules! a { () => {} } macro_rules! b { () => {} }",
+    /// )
+    /// .unwrap();
+    /// let selector = Selector::try_from("_macro").unwrap().macro_export(true);
+    /// assert_eq!(selector.apply_to(&file).len(), 1);
+    /// ```
+    pub fn macro_export(mut self, macro_export: bool) -> Self {
+        self.options.macro_export = Some(macro_export);
+        self
+    }
+
+    /// Only match trait and impl members that are methods, taking `self` in some form.
+    /// Associated functions, constants and types aren't matched. Other results aren't
+    /// affected.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("impl A { fn new() {} fn get(&self) {} }").unwrap();
+    /// let selector = Selector::try_from("A::_").unwrap().methods_only();
+    /// match &selector.apply_to(&file)[0] {
+    ///     syn::Item::Impl(item) => assert_eq!(item.items.len(), 1),
+    ///     _ => panic!("Expected an impl"),
+    /// }
+    /// ```
+    pub fn methods_only(mut self) -> Self {
+        self.options.methods_only = true;
+        self
+    }
+
+    /// Set whether results are grouped by kind, rather than returned in source order.
+    ///
+    /// Grouped results list imports and modules first, then macros, types and traits, then
+    /// functions, constants and statics, and finally impl blocks. Results of the same kind
+    /// stay in source order. This is off by default.
+    pub fn group_by_kind(mut self, group_by_kind: bool) -> Self {
+        self.options.group_by_kind = group_by_kind;
+        self
+    }
+
+    /// Also return up to `count` items before and after each result in the file, module or
+    /// function body it was found in, like `grep -C`. Items near more than one result are
+    /// only returned once, and everything stays in source order. This is 0 by default.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("fn a() {} fn b() {} fn c() {} fn d() {}").unwrap();
+    /// let results = Selector::try_from("b").unwrap().with_siblings(1).apply_to(&file);
+    /// assert_eq!(results.len(), 3);
+    /// ```
+    pub fn with_siblings(mut self, count: usize) -> Self {
+        self.options.with_siblings = count;
+        self
+    }
+
+    /// Check if any segment of this selector is a wildcard, and can match items with
+    /// different names.
+    pub fn has_wildcard(&self) -> bool {
+        self.segments.iter().any(SelectorSegment::is_wildcard)
+    }
+
+    /// Check if any segment of this selector is `**`, which can match any number of levels.
+    pub(crate) fn has_any_depth(&self) -> bool {
+        self.segments.iter().any(SelectorSegment::is_any_depth)
+    }
+
+    /// Get the positions of this selector's wildcard segments, counting from zero.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let selector = Selector::try_from("a::_::c::_fn[0..2]").unwrap();
+    /// assert_eq!(selector.wildcard_indices(), vec![1, 3]);
+    /// ```
+    pub fn wildcard_indices(&self) -> Vec<usize> {
+        self.segments
+            .iter()
+            .enumerate()
+            .filter(|(_, segment)| segment.is_wildcard())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Check if a path, such as `a::b::C`, would be matched by this selector, without
+    /// searching any source.
+    ///
+    /// Each segment of `path` must be an identifier or a field index. Since a path doesn't say
+    /// what kind of item it names or where the item is in its parent, wildcards limited to a
+    /// kind or range, such as `_fn[0..2]`, match any name. Unanchored selectors match the end
+    /// of `path`.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let selector = Selector::try_from("a::_::c").unwrap();
+    /// assert!(selector.matches("a::b::c"));
+    /// assert!(!selector.matches("a::c"));
+    /// ```
+    pub fn matches(&self, path: &str) -> bool {
+        let parts = path.split("::").collect::<Vec<_>>();
+        if self.options.anchored {
+            matches_parts(&self.segments, &parts)
+        } else {
+            (0..parts.len()).any(|start| matches_parts(&self.segments, &parts[start..]))
+        }
+    }
+
     /// Use this selector to search a file, returning the list of items that match the selector.
     pub fn apply_to(&self, file: &syn::File) -> Vec<Item> {
-        let mut search = Search::new(self);
-        search.search_file(file);
-        search.results
+        self.apply_to_borrowed(file)
+            .into_iter()
+            .map(Cow::into_owned)
+            .collect()
+    }
+
+    /// Use this selector to search a sequence of items as if they were the top level of a
+    /// file, such as the contents of a module or items extracted from another search.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a { mod b { fn c() {} } }").unwrap();
+    /// let a = Selector::try_from("a").unwrap().apply_to(&file);
+    /// let results = Selector::try_from("a::b::c").unwrap().apply_to_items(&a);
+    /// assert_eq!(results.len(), 1);
+    /// ```
+    pub fn apply_to_items<T: AsRef<[Item]> + ?Sized>(&self, items: &T) -> Vec<Item> {
+        let mut walk = Walk::new(self);
+        walk.search_items(items.as_ref());
+        walk.into_results()
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], without cloning the
+    /// results that appear verbatim in the file.
+    ///
+    /// Results that the search had to change, such as a trait filtered down to the matching
+    /// methods or an item given the `cfg` attributes of its enclosing module, are owned. The
+    /// rest borrow from `file`.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use std::borrow::Cow;
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a { struct B; } trait C { fn d(); }").unwrap();
+    /// let results = Selector::try_from("a::B").unwrap().apply_to_borrowed(&file);
+    /// assert!(matches!(results[0], Cow::Borrowed(_)));
+    /// let results = Selector::try_from("C::d").unwrap().apply_to_borrowed(&file);
+    /// assert!(matches!(results[0], Cow::Owned(_)));
+    /// ```
+    pub fn apply_to_borrowed<'f>(&self, file: &'f syn::File) -> Vec<Cow<'f, Item>> {
+        if self.is_single_name() {
+            // A single name can only match top-level items, so skip the recursive search.
+            let mut results = top_level_items(file)
+                .into_iter()
+                .filter(|item| self.matches_item_name(item))
+                .map(|item| retain_attrs(item, &self.options))
+                .collect::<Vec<_>>();
+            sort_by_kind(&mut results, &self.options, |item| item);
+            results
+        } else {
+            let mut walk = Walk::new(self);
+            walk.search_file(file);
+            walk.into_borrowed_results()
+        }
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], also returning a record
+    /// of the decisions the search made, such as which items matched each segment and which
+    /// items had nothing inside them that matched the next one.
+    ///
+    /// Diagnostics are meant to explain unexpected results to a person, so the wording of
+    /// their messages may change between releases.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("mod a { fn c() {} }").unwrap();
+    /// let (results, diagnostics) = Selector::try_from("a::b")
+    ///     .unwrap()
+    ///     .apply_to_with_diagnostics(&file);
+    /// assert!(results.is_empty());
+    /// assert!(diagnostics
+    ///     .iter()
+    ///     .any(|diagnostic| diagnostic.message == "nothing inside `a` (mod) matched `b`"));
+    /// ```
+    pub fn apply_to_with_diagnostics(&self, file: &syn::File) -> (Vec<Item>, Vec<Diagnostic>) {
+        let mut walk = Walk::new(self).collect_diagnostics();
+        walk.search_file(file);
+        let diagnostics = walk.take_diagnostics();
+        (walk.into_results(), diagnostics)
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], returning each result
+    /// with the `cfg` predicates guarding it. See [`crate::select_with_cfgs`].
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(
+    ///     r#"#[cfg(unix)] struct H(u8); #[cfg(not(unix))] struct H(u16);"#,
+    /// ).unwrap();
+    /// let results = Selector::try_from("H")
+    ///     .unwrap()
+    ///     .merge_cfg_variants(true)
+    ///     .apply_to_with_cfgs(&file);
+    /// assert_eq!(results.len(), 1);
+    /// assert_eq!(results[0].variants.len(), 2);
+    /// ```
+    pub fn apply_to_with_cfgs(&self, file: &syn::File) -> Vec<Match> {
+        let mut walk = Walk::new(self);
+        walk.search_file(file);
+        let matches = walk
+            .into_found_results()
+            .into_iter()
+            .map(|found| (found.path, Match::from(found.item.into_owned())));
+
+        if !self.options.merge_cfg_variants {
+            return matches.map(|(_, result)| result).collect();
+        }
+
+        // Results are merged when they have the same path and kind, so a module and a
+        // function with the same name stay apart.
+        let mut groups: Vec<(Vec<String>, ItemKind, Vec<Match>)> = Vec::new();
+        for (path, result) in matches {
+            let kind = ItemKind::of(&result.item);
+            match groups
+                .iter_mut()
+                .find(|(own_path, own_kind, _)| *own_path == path && *own_kind == kind)
+            {
+                Some((_, _, variants)) => variants.push(result),
+                None => groups.push((path, kind, vec![result])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, _, variants)| Match::merge(variants))
+            .collect()
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], loading the contents of
+    /// modules declared in another file, such as `mod foo;`, with `loader`.
+    ///
+    /// This works like [`Selector::with_mod_resolver`], and is used instead of any resolver
+    /// registered on the selector, but the loader only needs to live as long as this search
+    /// and can keep state between calls, such as a cache of parsed files.
+    ///
+    /// Like the callback of [`Selector::with_mod_path_resolver`], the loader gets the names of
+    /// the items from the root of the searched file to the module, and the value of the
+    /// module's `#[path = "..."]` attribute, if it has one.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str(r#"mod a; #[path = "c_impl.rs"] mod c;"#).unwrap();
+    /// let mut requested = Vec::new();
+    /// let results = Selector::try_from("_::B")
+    ///     .unwrap()
+    ///     .apply_to_with_loader(&file, |path, file_path| {
+    ///         requested.push((path.join("::"), file_path.map(String::from)));
+    ///         syn::parse_str("struct B;").ok()
+    ///     });
+    /// assert_eq!(results.len(), 2);
+    /// assert_eq!(
+    ///     requested,
+    ///     vec![("a".into(), None), ("c".into(), Some("c_impl.rs".into()))]
+    /// );
+    /// ```
+    pub fn apply_to_with_loader(
+        &self,
+        file: &syn::File,
+        loader: impl FnMut(&[String], Option<&str>) -> Option<syn::File>,
+    ) -> Vec<Item> {
+        let loader = RefCell::new(loader);
+        let load =
+            |path: &[String], file_path: Option<&str>| (loader.borrow_mut())(path, file_path);
+        let mut walk = Walk::new(self).with_loader(&load);
+        walk.search_file(file);
+        walk.into_results()
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], returning each result
+    /// with whether it's a direct child of the file. See [`crate::select_with_top_level`].
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::Selector;
+    /// let file: syn::File = syn::parse_str("struct A; fn b() { struct A; }").unwrap();
+    /// let results = Selector::ends_with("A").unwrap().apply_to_with_top_level(&file);
+    /// let top_level = results.iter().map(|(_, top_level)| *top_level).collect::<Vec<_>>();
+    /// assert_eq!(top_level, vec![true, false]);
+    /// ```
+    pub fn apply_to_with_top_level(&self, file: &syn::File) -> Vec<(Item, bool)> {
+        let mut walk = Walk::new(self);
+        walk.search_file(file);
+        walk.into_found_results()
+            .into_iter()
+            .map(|found| {
+                let top_level = found.is_top_level();
+                (found.item.into_owned(), top_level)
+            })
+            .collect()
+    }
+
+    /// Use this selector to search a file like [`Selector::apply_to`], but return an error
+    /// explaining which segment failed if nothing matched.
+    ///
+    /// The error's kind is [`ContainerNotFound`](crate::ErrorKind::ContainerNotFound) if no
+    /// item matched one of the leading segments of the path, so the rest of the path was never
+    /// searched, and [`LeafNotFound`](crate::ErrorKind::LeafNotFound) if only the last segment failed to match.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::{ErrorKind, Selector};
+    /// let file: syn::File = syn::parse_str("mod a { fn b() {} }").unwrap();
+    /// let selector = Selector::try_from("a::c::d").unwrap();
+    /// match selector.try_apply_to(&file) {
+    ///     Err(error) => assert!(matches!(
+    ///         error.kind(),
+    ///         ErrorKind::ContainerNotFound { position: 1, .. }
+    ///     )),
+    ///     Ok(_) => panic!("Expected an error"),
+    /// }
+    /// ```
+    pub fn try_apply_to(&self, file: &syn::File) -> Result<Vec<Item>, Error> {
+        let results = self.apply_to(file);
+        if !results.is_empty() {
+            return Ok(results);
+        }
+
+        // Find the longest prefix of the path that matches something; the segment after it
+        // is the one that failed. A prefix can't end with `**`, so when the failed segment is
+        // a `**`, it's the one after it that failed.
+        let mut position = (1..self.len())
+            .rev()
+            .filter(|len| !self.segments[len - 1].is_any_depth())
+            .find(|len| !self.prefix(*len).apply_to(file).is_empty())
+            .unwrap_or(0);
+        while self.segments[position].is_any_depth() {
+            position += 1;
+        }
+
+        Err(Error::not_found(
+            position,
+            self.segments[position].to_string(),
+            position == self.len() - 1,
+        ))
+    }
+
+    /// Use this selector to search a file for a result of the expected kind, such as a function
+    /// a build script is going to process.
+    ///
+    /// If several results match, the first of the expected kind is returned. The error is the
+    /// one [`Selector::try_apply_to`] returns if nothing matched, or has the kind
+    /// [`KindMismatch`](crate::ErrorKind::KindMismatch) with the kind of the first result if
+    /// none of the results has the expected kind.
+    ///
+    /// # Usage
+    /// ```rust,edition2018
+    /// use syn_select::{ErrorKind, ItemKind, Selector};
+    /// let file: syn::File = syn::parse_str("mod a { struct b; }").unwrap();
+    /// let selector = Selector::try_from("a::b").unwrap();
+    /// assert!(selector.expect_kind(&file, ItemKind::Struct).is_ok());
+    /// match selector.expect_kind(&file, ItemKind::Fn) {
+    ///     Err(error) => assert!(matches!(
+    ///         error.kind(),
+    ///         ErrorKind::KindMismatch { expected: ItemKind::Fn, found: ItemKind::Struct }
+    ///     )),
+    ///     Ok(_) => panic!("Expected an error"),
+    /// }
+    /// ```
+    pub fn expect_kind(&self, file: &syn::File, kind: ItemKind) -> Result<Item, Error> {
+        let results = self.try_apply_to(file)?;
+        let found = ItemKind::of(&results[0]);
+        results
+            .into_iter()
+            .find(|item| ItemKind::of(item) == kind)
+            .ok_or_else(|| Error::kind_mismatch(kind, found))
+    }
+
+    /// Create an unanchored selector that matches items with any of the given names, at
+    /// any depth.
+    pub(crate) fn any_of(names: &[&str]) -> Result<Self, Error> {
+        let names = names
+            .iter()
+            .map(|name| {
+                syn::parse_str::<Ident>(name)
+                    .map(|ident| ident.to_string())
+                    .map_err(|err| Error::invalid_segment((*name).into(), Some(err)))
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Selector {
+            segments: vec![SelectorSegment::Names(names)],
+            mod_resolver: None,
+            options: Options {
+                anchored: false,
+                ..Options::default()
+            },
+        })
+    }
+
+    /// Check if this selector is a single, anchored name such as `Foo`.
+    fn is_single_name(&self) -> bool {
+        self.options.anchored
+            && self.options.with_siblings == 0
+            && matches!(self.segments.as_slice(), [SelectorSegment::Ident(_)])
+    }
+
+    /// Check if a top-level item is matched by this selector, which must be a single segment.
+    pub(crate) fn matches_item_name(&self, item: &Item) -> bool {
+        Walk::new(self).is_result(0, item)
+    }
+
+    /// Get a copy of this selector containing only its first `len` segments.
+    ///
+    /// Filters apply to the results of the whole selector, not to the containers matched by
+    /// its leading segments, so they're not kept.
+    fn prefix(&self, len: usize) -> Self {
+        let mut prefix = self.clone();
+        prefix.segments.truncate(len);
+        prefix.options.filters.clear();
+        prefix.options.macro_export = None;
+        prefix
     }
 
     pub(crate) fn part(&self, index: usize) -> &SelectorSegment {
@@ -41,6 +899,22 @@ impl Selector {
     pub(crate) fn len(&self) -> usize {
         self.segments.len()
     }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// Load the contents of an out-of-line module using the registered resolver, if any.
+    /// `file_path` is the value of the module's `#[path]` attribute.
+    pub(crate) fn resolve_mod(
+        &self,
+        path: &[String],
+        file_path: Option<&str>,
+    ) -> Option<syn::File> {
+        self.mod_resolver
+            .as_ref()
+            .and_then(|resolver| (resolver.0)(path, file_path))
+    }
 }
 
 impl fmt::Display for Selector {
@@ -54,6 +928,40 @@ impl fmt::Display for Selector {
     }
 }
 
+impl From<Selector> for String {
+    fn from(selector: Selector) -> Self {
+        selector.to_string()
+    }
+}
+
+impl From<&Selector> for String {
+    fn from(selector: &Selector) -> Self {
+        selector.to_string()
+    }
+}
+
+impl PartialEq for Selector {
+    fn eq(&self, other: &Self) -> bool {
+        self.segments == other.segments && self.options == other.options
+    }
+}
+
+impl Eq for Selector {}
+
+impl PartialOrd for Selector {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Selector {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.segments
+            .cmp(&other.segments)
+            .then_with(|| self.options.cmp(&other.options))
+    }
+}
+
 impl FromStr for Selector {
     type Err = Error;
 
@@ -64,45 +972,310 @@ impl FromStr for Selector {
             return Err(Error::empty_path());
         }
 
-        for segment in input.split("::") {
-            match segment.parse() {
-                Ok(seg) => segments.push(seg),
-                Err(_) => return Err(Error::invalid_segment(segment.into())),
+        // Identifiers can't contain `:`, so splitting on `::` never cuts a segment in half.
+        // Anything that doesn't split cleanly, such as `a:::b`, is rejected below.
+        for (position, segment) in input.split("::").enumerate() {
+            if segment.is_empty() {
+                return Err(Error::empty_segment(position));
             }
+
+            segments.push(segment.parse()?);
+        }
+
+        // `**` stands for the levels between two segments, so it can't end a path.
+        if let Some(SelectorSegment::AnyDepth) = segments.last() {
+            return Err(Error::invalid_segment("**".into(), None));
+        }
+
+        Ok(Selector {
+            segments,
+            mod_resolver: None,
+            options: Options::default(),
+        })
+    }
+}
+
+/// Settings which change how a search treats the items it encounters.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Options {
+    /// Require the first segment to match a top-level item.
+    pub anchored: bool,
+    /// Search items declared in `let` initializers inside function bodies.
+    pub search_locals: bool,
+    /// Match `extern crate` items by the crate's name instead of its local rename.
+    pub extern_crate_original_name: bool,
+    /// Match `pub use` items by the names they re-export.
+    pub match_reexports: bool,
+    /// Match all `use` items, not just `pub` ones, by the names they import.
+    pub match_imports: bool,
+    /// Conditions every result must satisfy.
+    pub filters: Vec<Filter>,
+    /// Sort results by kind instead of leaving them in source order.
+    pub group_by_kind: bool,
+    /// Only match trait methods that have a default body, or only those that don't.
+    pub with_default: Option<bool>,
+    /// Only match trait and impl members that take `self`.
+    pub methods_only: bool,
+    /// How many items before and after each result in its container to return as well.
+    pub with_siblings: usize,
+    /// Drop results nested more deeply than another result of the same name.
+    pub outermost_only: bool,
+    /// The paths of the attributes to keep on results, or `None` to keep them all.
+    pub keep_only_attrs: Option<Vec<String>>,
+    /// Merge results that share a path but differ by `cfg` in `apply_to_with_cfgs`.
+    pub merge_cfg_variants: bool,
+    /// Only match `macro_rules!` definitions that are exported, or only those that aren't.
+    pub macro_export: Option<bool>,
+    /// The kinds of containers to return whole when the rest of the path matches inside them.
+    pub stop_at_kinds: Vec<ItemKind>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Options {
+            anchored: true,
+            search_locals: false,
+            extern_crate_original_name: false,
+            match_reexports: false,
+            match_imports: false,
+            filters: Vec::new(),
+            group_by_kind: false,
+            with_default: None,
+            methods_only: false,
+            with_siblings: 0,
+            outermost_only: false,
+            keep_only_attrs: None,
+            merge_cfg_variants: false,
+            macro_export: None,
+            stop_at_kinds: Vec::new(),
         }
+    }
+}
+
+type ResolveFn = dyn Fn(&[String], Option<&str>) -> Option<syn::File> + Send + Sync;
+
+/// A callback that loads the contents of out-of-line modules.
+#[derive(Clone)]
+struct ModResolver(Arc<ResolveFn>);
 
-        Ok(Selector { segments })
+impl fmt::Debug for ModResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ModResolver")
     }
 }
 
 /// One segment of a selector path
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub(crate) enum SelectorSegment {
     /// A specific ident that must be exactly equal to match.
     Ident(String),
-    /// A wildcard that matches any ident.
-    Wildcard,
+    /// A pattern where each `*` stands for any run of characters, e.g. `handle_*`.
+    Glob(String),
+    /// A wildcard that matches any ident, optionally limited to one kind of item, e.g. `_fn`,
+    /// and to a range of positions within the parent, e.g. `_[0..10]`. Positions count every
+    /// item in the parent, whatever the kind, so `_fn[0..3]` matches the functions among the
+    /// first three items.
+    Wildcard {
+        kind: Option<ItemKind>,
+        range: Option<PositionRange>,
+    },
+    /// Any one ident, written `*`. This matches the same things as an untyped `_`, but is kept
+    /// apart so that the selector displays as it was written.
+    Star,
+    /// Any number of levels, including none, written `**`. This is never the last segment.
+    AnyDepth,
+    /// The position of a field in a tuple struct or tuple variant.
+    Index(usize),
+    /// Any one of a set of idents. This can't be written in a selector string.
+    Names(Vec<String>),
+}
+
+impl SelectorSegment {
+    /// Check if this segment can match more than one name.
+    fn is_wildcard(&self) -> bool {
+        match self {
+            SelectorSegment::Wildcard { .. } | SelectorSegment::Star => true,
+            SelectorSegment::AnyDepth | SelectorSegment::Glob(_) => true,
+            SelectorSegment::Ident(_) | SelectorSegment::Index(_) => false,
+            SelectorSegment::Names(_) => false,
+        }
+    }
+
+    /// Check if this segment is a wildcard limited to one kind of item, such as `_use`.
+    pub(crate) fn is_typed_wildcard(&self) -> bool {
+        matches!(self, SelectorSegment::Wildcard { kind: Some(_), .. })
+    }
+
+    /// Check if this segment is `**`, which matches any number of levels.
+    pub(crate) fn is_any_depth(&self) -> bool {
+        matches!(self, SelectorSegment::AnyDepth)
+    }
+
+    /// Check if this segment matches the field at `index` in its parent's fields.
+    pub(crate) fn matches_field(&self, index: usize, field: &syn::Field) -> bool {
+        match (self, &field.ident) {
+            (SelectorSegment::Wildcard { kind, .. }, _) => kind.is_none(),
+            (SelectorSegment::Star, _) => true,
+            (SelectorSegment::Index(own), None) => *own == index,
+            (SelectorSegment::Ident(own), Some(ident)) => ident == own,
+            (SelectorSegment::Glob(pattern), Some(ident)) => glob_matches(pattern, ident),
+            (SelectorSegment::Names(names), Some(ident)) => names.iter().any(|own| ident == own),
+            _ => false,
+        }
+    }
+
+    /// Check if this segment permits a match at position `index` within the parent.
+    /// This is only restrictive for wildcards with a range.
+    pub(crate) fn includes_position(&self, index: usize) -> bool {
+        match self {
+            SelectorSegment::Wildcard {
+                range: Some(range), ..
+            } => range.contains(index),
+            _ => true,
+        }
+    }
+
+    /// Check if this segment matches one segment of a path, which is either an identifier
+    /// or a field index.
+    fn matches_part(&self, part: &str) -> bool {
+        if part.bytes().all(|byte| byte.is_ascii_digit()) {
+            return match part.parse::<SelectorSegment>() {
+                Ok(SelectorSegment::Index(index)) => match self {
+                    SelectorSegment::Index(own) => *own == index,
+                    SelectorSegment::Wildcard { kind, .. } => kind.is_none(),
+                    SelectorSegment::Star => true,
+                    _ => false,
+                },
+                _ => false,
+            };
+        }
+
+        syn::parse_str::<Ident>(part).is_ok_and(|ident| self == &ident)
+    }
+
+    /// Check if this segment permits a match of the given kind of item. Enum variants
+    /// and fields aren't items, so they pass `None`.
+    /// This is only restrictive for wildcards limited to one kind.
+    pub(crate) fn includes_kind(&self, kind: Option<ItemKind>) -> bool {
+        match self {
+            SelectorSegment::Wildcard {
+                kind: Some(own), ..
+            } => kind == Some(*own),
+            _ => true,
+        }
+    }
 }
 
 impl FromStr for SelectorSegment {
     type Err = Error;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
-        if input == "_" {
-            return Ok(SelectorSegment::Wildcard);
+        match input {
+            "*" => return Ok(SelectorSegment::Star),
+            "**" => return Ok(SelectorSegment::AnyDepth),
+            _ => {}
+        }
+
+        if let Some(rest) = input.strip_prefix('_') {
+            let (kind, range) = match rest.split_once('[') {
+                Some((kind, range)) => (kind, Some(range)),
+                None => (rest, None),
+            };
+
+            // An unrecognized kind means this is an ident that starts with an underscore.
+            let kind = match kind {
+                "" => Some(None),
+                kind => ItemKind::from_name(kind).map(Some),
+            };
+
+            if let Some(kind) = kind {
+                let range = match range {
+                    Some(range) => Some(
+                        range
+                            .strip_suffix(']')
+                            .and_then(|range| range.parse().ok())
+                            .ok_or_else(|| Error::invalid_segment(input.into(), None))?,
+                    ),
+                    None => None,
+                };
+
+                return Ok(SelectorSegment::Wildcard { kind, range });
+            }
+        }
+
+        // A `*` inside a segment makes it a glob; the rest of it must be part of an identifier.
+        if input.contains('*') {
+            return syn::parse_str::<Ident>(&input.replace('*', "x"))
+                .map(|_| SelectorSegment::Glob(input.into()))
+                .map_err(|err| Error::invalid_segment(input.into(), Some(err)));
+        }
+
+        // Only accept the canonical form of an index so that it round-trips through `Display`.
+        if input.bytes().all(|byte| byte.is_ascii_digit()) {
+            return match input.parse::<usize>() {
+                Ok(index) if index.to_string() == input => Ok(SelectorSegment::Index(index)),
+                _ => Err(Error::invalid_segment(input.into(), None)),
+            };
         }
 
         syn::parse_str::<Ident>(input)
             .map(|ident| SelectorSegment::Ident(ident.to_string()))
-            .map_err(|_| Error::invalid_segment(input.into()))
+            .map_err(|err| Error::invalid_segment(input.into(), Some(err)))
     }
 }
 
 impl PartialEq<Ident> for SelectorSegment {
     fn eq(&self, other: &Ident) -> bool {
         match self {
-            SelectorSegment::Wildcard => true,
+            SelectorSegment::Wildcard { .. } | SelectorSegment::Star => true,
+            SelectorSegment::AnyDepth => true,
             SelectorSegment::Ident(ident) => other == ident,
+            SelectorSegment::Glob(pattern) => glob_matches(pattern, other),
+            SelectorSegment::Index(_) => false,
+            SelectorSegment::Names(names) => names.iter().any(|own| other == own),
+        }
+    }
+}
+
+impl SelectorSegment {
+    /// Get the position of this kind of segment when segments are sorted.
+    fn sort_rank(&self) -> usize {
+        match self {
+            SelectorSegment::Ident(_) => 0,
+            SelectorSegment::Index(_) => 1,
+            SelectorSegment::Glob(_) => 2,
+            SelectorSegment::Wildcard { .. } => 3,
+            SelectorSegment::Star => 4,
+            SelectorSegment::AnyDepth => 5,
+            SelectorSegment::Names(_) => 6,
+        }
+    }
+}
+
+impl PartialOrd for SelectorSegment {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for SelectorSegment {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (SelectorSegment::Ident(own), SelectorSegment::Ident(other)) => own.cmp(other),
+            (SelectorSegment::Index(own), SelectorSegment::Index(other)) => own.cmp(other),
+            (SelectorSegment::Glob(own), SelectorSegment::Glob(other)) => own.cmp(other),
+            (
+                SelectorSegment::Wildcard { kind, range },
+                SelectorSegment::Wildcard {
+                    kind: other_kind,
+                    range: other_range,
+                },
+            ) => kind
+                .map(ItemKind::name)
+                .cmp(&other_kind.map(ItemKind::name))
+                .then_with(|| range.cmp(other_range)),
+            (SelectorSegment::Names(own), SelectorSegment::Names(other)) => own.cmp(other),
+            _ => self.sort_rank().cmp(&other.sort_rank()),
         }
     }
 }
@@ -110,8 +1283,109 @@ impl PartialEq<Ident> for SelectorSegment {
 impl fmt::Display for SelectorSegment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            SelectorSegment::Wildcard => "_".fmt(f),
-            SelectorSegment::Ident(ident) => ident.fmt(f),
+            SelectorSegment::Wildcard { kind, range } => {
+                f.write_str("_")?;
+                if let Some(kind) = kind {
+                    kind.fmt(f)?;
+                }
+                if let Some(range) = range {
+                    write!(f, "[{}]", range)?;
+                }
+                Ok(())
+            }
+            SelectorSegment::Star => f.write_str("*"),
+            SelectorSegment::AnyDepth => f.write_str("**"),
+            SelectorSegment::Ident(ident) | SelectorSegment::Glob(ident) => ident.fmt(f),
+            SelectorSegment::Index(index) => index.fmt(f),
+            SelectorSegment::Names(names) => write!(f, "{{{}}}", names.join(", ")),
         }
     }
 }
+
+/// A range of positions within a parent that a wildcard may match, such as `0..10`.
+/// Either bound can be omitted. Bounds past the end of the parent match nothing
+/// rather than raising an error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct PositionRange {
+    start: Option<usize>,
+    end: Option<usize>,
+}
+
+impl PositionRange {
+    fn contains(&self, index: usize) -> bool {
+        self.start.map_or(true, |start| index >= start) && self.end.map_or(true, |end| index < end)
+    }
+}
+
+impl FromStr for PositionRange {
+    type Err = ();
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let (start, end) = input.split_once("..").ok_or(())?;
+        Ok(PositionRange {
+            start: if start.is_empty() {
+                None
+            } else {
+                Some(start.parse().map_err(|_| ())?)
+            },
+            end: if end.is_empty() {
+                None
+            } else {
+                Some(end.parse().map_err(|_| ())?)
+            },
+        })
+    }
+}
+
+impl fmt::Display for PositionRange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(start) = self.start {
+            start.fmt(f)?;
+        }
+
+        f.write_str("..")?;
+
+        if let Some(end) = self.end {
+            end.fmt(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check if the segments of a path match `segments`, with `**` standing for any number of them.
+fn matches_parts(segments: &[SelectorSegment], parts: &[&str]) -> bool {
+    match segments.split_first() {
+        None => parts.is_empty(),
+        Some((SelectorSegment::AnyDepth, rest)) => {
+            (0..=parts.len()).any(|skip| matches_parts(rest, &parts[skip..]))
+        }
+        Some((segment, rest)) => match parts.split_first() {
+            Some((part, parts)) => segment.matches_part(part) && matches_parts(rest, parts),
+            None => false,
+        },
+    }
+}
+
+/// Check if `ident` matches a glob `pattern`, where each `*` stands for any run of characters,
+/// including none. Raw identifiers are matched without their `r#` prefix.
+fn glob_matches(pattern: &str, ident: &Ident) -> bool {
+    let name = ident.unraw().to_string();
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match name.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let mut parts = parts.collect::<Vec<_>>();
+    let last = parts.pop().unwrap_or_default();
+    for part in parts {
+        match rest.find(part) {
+            Some(start) => rest = &rest[start + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.ends_with(last)
+}
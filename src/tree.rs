@@ -0,0 +1,38 @@
+//! Tree-shaped search results, which keep the nesting of the items a search passed through.
+
+use syn::Item;
+
+use crate::search::Found;
+
+/// A search result with the results found inside it.
+///
+/// Nodes with children are the containers the search descended through, such as modules,
+/// functions and traits, and hold the whole container. Leaves are the items [`crate::select`]
+/// would return.
+#[derive(Clone)]
+pub struct MatchNode {
+    /// The matched item, or the container the search passed through.
+    pub item: Item,
+    /// The results found inside `item`, in document order.
+    pub children: Vec<MatchNode>,
+}
+
+impl MatchNode {
+    /// Get the leaves under this node, in document order.
+    pub fn leaves(&self) -> Vec<&Item> {
+        if self.children.is_empty() {
+            return vec![&self.item];
+        }
+
+        self.children.iter().flat_map(MatchNode::leaves).collect()
+    }
+}
+
+impl From<Found<'_>> for MatchNode {
+    fn from(found: Found<'_>) -> Self {
+        MatchNode {
+            item: found.item.into_owned(),
+            children: found.children.into_iter().map(MatchNode::from).collect(),
+        }
+    }
+}